@@ -1,9 +1,48 @@
+mod merkle_airdrop;
+
 use scrypto::prelude::*;
 
+// Data of the claim ticket NFT that entitles a recipient to claim their share of a claim airdrop
+#[derive(NftData)]
+struct ClaimTicket {
+    amount: Decimal,
+    expiry_epoch: u64,
+}
+
+// Data of the vesting ticket NFT that entitles a recipient to claim their share of a vesting airdrop as it vests
+#[derive(NftData)]
+struct VestingTicket {
+    // index of the vesting pool that holds the tokens
+    pool: u64,
+    amount: Decimal,
+    start_epoch: u64,
+    end_epoch: u64,
+    // the share vests in steps of this many epochs, 1 means linear vesting
+    step_epochs: u64,
+    #[scrypto(mutable)]
+    claimed: Decimal,
+}
+
 blueprint! {
     struct Airdrop {
         admin_badge: ResourceDef,
-        recipients: Vec<Address>,
+        recipients: Vec<(Address, Decimal)>,
+        total_weight: Decimal,
+
+        // Badge that allows the component to mint and burn claim tickets
+        minter: Vault,
+        claim_ticket: ResourceDef,
+        // Tokens of the current claim airdrop that have not been claimed yet
+        claim_pool: Option<Vault>,
+        claim_expiry_epoch: u64,
+        // Counts the claim airdrops, so that the entitlements of earlier airdrops can't be issued again
+        claim_round: u64,
+        // The amount every recipient of a claim airdrop is entitled to, until their claim ticket is issued
+        claim_entitlements: LazyMap<(u64, Address), Decimal>,
+
+        vesting_ticket: ResourceDef,
+        // Tokens of all vesting airdrops that have not been claimed yet, one vault per airdrop
+        vesting_pools: Vec<Vault>,
     }
 
     impl Airdrop {
@@ -11,9 +50,33 @@ blueprint! {
             let admin_badge = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
                                 .initial_supply_fungible(1);
 
+            let minter = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+                                .initial_supply_fungible(1);
+
+            let claim_ticket = ResourceBuilder::new_non_fungible()
+                                .metadata("name", "Airdrop Claim Ticket")
+                                .flags(MINTABLE | BURNABLE)
+                                .badge(minter.resource_def(), MAY_MINT | MAY_BURN)
+                                .no_initial_supply();
+
+            let vesting_ticket = ResourceBuilder::new_non_fungible()
+                                .metadata("name", "Airdrop Vesting Ticket")
+                                .flags(MINTABLE | BURNABLE | INDIVIDUAL_METADATA_MUTABLE)
+                                .badge(minter.resource_def(), MAY_MINT | MAY_BURN | MAY_CHANGE_INDIVIDUAL_METADATA)
+                                .no_initial_supply();
+
             let component = Self {
                 admin_badge: admin_badge.resource_def(),
-                recipients: Vec::new()
+                recipients: Vec::new(),
+                total_weight: Decimal::zero(),
+                minter: Vault::with_bucket(minter),
+                claim_ticket,
+                claim_pool: None,
+                claim_expiry_epoch: 0,
+                claim_round: 0,
+                claim_entitlements: LazyMap::new(),
+                vesting_ticket,
+                vesting_pools: Vec::new()
             }
             .instantiate();
 
@@ -21,8 +84,11 @@ blueprint! {
         }
 
         #[auth(admin_badge)]
-        pub fn add_recipient(&mut self, recipient: Address) {
-            self.recipients.push(recipient);    
+        pub fn add_recipient(&mut self, recipient: Address, weight: Decimal) {
+            assert!(weight > Decimal::zero(), "The weight of a recipient must be positive");
+
+            self.recipients.push((recipient, weight));
+            self.total_weight += weight;
         }
 
         #[auth(admin_badge)]
@@ -30,20 +96,181 @@ blueprint! {
             let num_recipients = self.recipients.len();
             assert!(num_recipients > 0, "You must register at least one recipient before performing an airdrop");
 
-            // Calculate the amount of tokens each recipient can receive
-            let amount_per_recipient = tokens.amount() / Decimal::from(num_recipients as i128);
+            for (address, amount) in self.shares(tokens.amount()) {
+                Account::from(address).deposit(tokens.take(amount));
+            }
+        }
+
+        /// Funds a claim pool with the given `tokens` instead of pushing them to the recipients.
+        /// Only the share of every recipient is recorded, no tokens or tickets are sent to their accounts.
+        /// Recipients get a claim ticket NFT for their share from `issue_ticket`, which they can redeem by calling `claim`
+        /// until (and including) the `expiry_epoch`. Afterwards the admin can reclaim all unclaimed tokens.
+        #[auth(admin_badge)]
+        pub fn perform_claim_airdrop(&mut self, tokens: Bucket, expiry_epoch: u64) {
+            let num_recipients = self.recipients.len();
+            assert!(num_recipients > 0, "You must register at least one recipient before performing an airdrop");
+            assert!(expiry_epoch >= Context::current_epoch(), "The expiry epoch must not be in the past");
+            assert!(self.claim_pool.is_none(), "The unclaimed tokens of the previous claim airdrop must be reclaimed first");
+
+            self.claim_round += 1;
+            for (address, amount) in self.shares(tokens.amount()) {
+                // A recipient may have been added more than once
+                let key = (self.claim_round, address);
+                let entitlement = self.claim_entitlements.get(&key).unwrap_or(Decimal::zero());
+                self.claim_entitlements.insert(key, entitlement + amount);
+            }
+
+            self.claim_pool = Some(Vault::with_bucket(tokens));
+            self.claim_expiry_epoch = expiry_epoch;
+        }
+
+        /// Mints the claim ticket for the share of the given `recipient` in the current claim airdrop
+        /// and deposits it to their account. Anyone may call this, the ticket always goes to the recipient.
+        pub fn issue_ticket(&mut self, recipient: Address) {
+            assert!(self.claim_pool.is_some(), "There are no tokens to claim");
+            assert!(Context::current_epoch() <= self.claim_expiry_epoch, "The claim airdrop has expired");
 
-            // Send that amount to all but the last recipients
+            let key = (self.claim_round, recipient);
+            let amount = self.claim_entitlements.get(&key).unwrap_or(Decimal::zero());
+            assert!(amount > Decimal::zero(), "The recipient has no claim ticket to issue");
+            self.claim_entitlements.insert(key, Decimal::zero());
+
+            let ticket_data = ClaimTicket {
+                amount,
+                expiry_epoch: self.claim_expiry_epoch,
+            };
+            let ticket = self.minter.authorize(|auth| {
+                self.claim_ticket.mint_nft(Uuid::generate(), ticket_data, auth)
+            });
+            Account::from(recipient).deposit(ticket);
+        }
+
+        /// Redeems the given claim tickets for the tokens they represent.
+        /// The tickets are burned.
+        pub fn claim(&mut self, tickets: Bucket) -> Bucket {
+            assert!(tickets.resource_def() == self.claim_ticket, "The supplied bucket does not contain claim tickets");
+            assert!(!tickets.is_empty(), "The supplied bucket is empty");
+            assert!(self.claim_pool.is_some(), "There are no tokens to claim");
+
+            let mut amount = Decimal::zero();
+            for ticket in tickets.get_nfts::<ClaimTicket>() {
+                let ticket_data = ticket.data();
+                assert!(Context::current_epoch() <= ticket_data.expiry_epoch, "The claim ticket has expired");
+                amount += ticket_data.amount;
+            }
+
+            self.minter.authorize(|auth| tickets.burn_with_auth(auth));
+            self.claim_pool.as_ref().unwrap().take(amount)
+        }
+
+        /// Returns all tokens of the current claim airdrop that have not been claimed before the expiry epoch.
+        #[auth(admin_badge)]
+        pub fn reclaim_unclaimed(&mut self) -> Bucket {
+            assert!(self.claim_pool.is_some(), "There is no claim airdrop to reclaim tokens from");
+            assert!(Context::current_epoch() > self.claim_expiry_epoch, "The claim airdrop has not expired yet");
+
+            let unclaimed = self.claim_pool.as_ref().unwrap().take_all();
+            self.claim_pool = None;
+            unclaimed
+        }
+
+        /// Locks the given `tokens` in a new vesting pool that releases them to the recipients over time.
+        /// Every recipient receives a vesting ticket NFT for their share. Nothing vests before the `start_epoch`
+        /// and the whole share has vested at the `end_epoch`. In between, the share vests linearly in steps
+        /// of `step_epochs`, so a step of 1 vests continuously and larger steps act as cliffs.
+        #[auth(admin_badge)]
+        pub fn perform_vesting_airdrop(&mut self, tokens: Bucket, start_epoch: u64, end_epoch: u64, step_epochs: u64) {
+            let num_recipients = self.recipients.len();
+            assert!(num_recipients > 0, "You must register at least one recipient before performing an airdrop");
+            assert!(end_epoch > start_epoch, "The end epoch must be after the start epoch");
+            assert!(step_epochs > 0, "The vesting steps must be at least one epoch long");
+
+            let pool = self.vesting_pools.len() as u64;
+            for (address, amount) in self.shares(tokens.amount()) {
+                let ticket_data = VestingTicket {
+                    pool,
+                    amount,
+                    start_epoch,
+                    end_epoch,
+                    step_epochs,
+                    claimed: Decimal::zero(),
+                };
+                let ticket = self.minter.authorize(|auth| {
+                    self.vesting_ticket.mint_nft(Uuid::generate(), ticket_data, auth)
+                });
+                Account::from(address).deposit(ticket);
+            }
+
+            self.vesting_pools.push(Vault::with_bucket(tokens));
+        }
+
+        /// Returns the amount of tokens that have vested for the given vesting ticket but have not been claimed yet.
+        pub fn available_vested(&self, ticket_id: u128) -> Decimal {
+            let ticket_data: VestingTicket = self.vesting_ticket.get_nft_data(ticket_id);
+            Self::vested(&ticket_data) - ticket_data.claimed
+        }
+
+        /// Pays out everything that has vested for the presented vesting ticket since the last claim.
+        /// The ticket is kept by the recipient for future claims.
+        pub fn claim_vested(&mut self, ticket: BucketRef) -> Bucket {
+            assert!(ticket.resource_def() == self.vesting_ticket, "The supplied bucket ref does not contain a vesting ticket");
+            assert!(ticket.amount() == Decimal::one(), "The supplied bucket ref must contain exactly one vesting ticket");
+
+            let ticket_id = ticket.get_nft_id();
+            let mut ticket_data: VestingTicket = self.vesting_ticket.get_nft_data(ticket_id);
+
+            let amount = Self::vested(&ticket_data) - ticket_data.claimed;
+            assert!(amount > Decimal::zero(), "No tokens have vested since the last claim");
+
+            // Record the claim in the ticket
+            let pool = ticket_data.pool as usize;
+            ticket_data.claimed += amount;
+            self.minter.authorize(|auth| {
+                self.vesting_ticket.update_nft_data(ticket_id, ticket_data, auth)
+            });
+
+            ticket.drop();
+            self.vesting_pools.get(pool).unwrap().take(amount)
+        }
+
+        // Splits `total_amount` pro-rata to the weights of the recipients
+        fn shares(&self, total_amount: Decimal) -> Vec<(Address, Decimal)> {
+            let num_recipients = self.recipients.len();
+            let mut shares = Vec::new();
+            let mut distributed = Decimal::zero();
+
+            // Calculate the pro-rata share of all but the last recipients
             for i in 0..(num_recipients - 1) {
-                let address = self.recipients.get(i).unwrap();
-                Account::from(*address).deposit(tokens.take(amount_per_recipient));
+                let (address, weight) = self.recipients.get(i).unwrap();
+                let amount = total_amount * *weight / self.total_weight;
+                shares.push((*address, amount));
+                distributed += amount;
+            }
+
+            // The last recipient receives the remaining tokens.
+            // This should be almost exactly their pro-rata share except for rounding errors. This way we can be sure
+            // not to loose any tokens and the rounding dust always ends up with the same recipient.
+            let (last_address, _) = self.recipients.get(num_recipients-1).unwrap();
+            shares.push((*last_address, total_amount - distributed));
+
+            shares
+        }
+
+        // Calculates the total amount of tokens that have vested for the given vesting ticket at the current epoch
+        fn vested(ticket_data: &VestingTicket) -> Decimal {
+            let current_epoch = Context::current_epoch();
+            if current_epoch < ticket_data.start_epoch {
+                return Decimal::zero();
+            }
+            if current_epoch >= ticket_data.end_epoch {
+                return ticket_data.amount;
             }
 
-            // Send the remaining tokens to the last recipient.
-            // This should be almost exactly the calculated amount except for rounding errors. This way we can be sure
-            // not to loose any tokens.
-            let last_address = self.recipients.get(num_recipients-1).unwrap();
-            Account::from(*last_address).deposit(tokens);
+            // Only whole steps count as vested
+            let elapsed = current_epoch - ticket_data.start_epoch;
+            let vested_epochs = elapsed - elapsed % ticket_data.step_epochs;
+            let duration = ticket_data.end_epoch - ticket_data.start_epoch;
+            ticket_data.amount * Decimal::from(vested_epochs) / Decimal::from(duration)
         }
     }
 }
@@ -0,0 +1,117 @@
+use scrypto::prelude::*;
+use sha2::{Digest, Sha256};
+
+blueprint! {
+    struct MerkleAirdrop {
+        admin_badge: ResourceDef,
+        tokens: Vault,
+        // SHA-256 Merkle root over all (index, address, amount) leaves
+        merkle_root: [u8; 32],
+        // Bitmap of the claimed leaf indexes, 128 leaves per entry
+        claimed: LazyMap<u64, u128>,
+        expiry_epoch: u64,
+    }
+
+    impl MerkleAirdrop {
+        /// Creates a new Merkle airdrop that distributes the given `tokens`.
+        ///
+        /// The `merkle_root` is the hex encoded root of a SHA-256 Merkle tree whose leaves are the hashes of
+        /// the fixed-width leaf encoding described in `leaf`. Inner nodes are the hash of their two children in ascending order,
+        /// so proofs don't need to encode whether a sibling is on the left or on the right.
+        /// Claims are possible until (and including) the `expiry_epoch`.
+        pub fn new(tokens: Bucket, merkle_root: String, expiry_epoch: u64) -> (Component, Bucket) {
+            assert!(!tokens.is_empty(), "The airdrop must be funded with tokens");
+            assert!(expiry_epoch >= Context::current_epoch(), "The expiry epoch must not be in the past");
+
+            let admin_badge = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+                                .initial_supply_fungible(1);
+
+            let component = Self {
+                admin_badge: admin_badge.resource_def(),
+                tokens: Vault::with_bucket(tokens),
+                merkle_root: Self::decode_hash(&merkle_root),
+                claimed: LazyMap::new(),
+                expiry_epoch
+            }
+            .instantiate();
+
+            (component, admin_badge)
+        }
+
+        /// Returns whether the leaf with the given `index` has already been claimed.
+        pub fn is_claimed(&self, index: u64) -> bool {
+            let word = self.claimed.get(&(index / 128)).unwrap_or(0);
+            word & (1u128 << (index % 128)) != 0
+        }
+
+        /// Claims the `amount` of tokens that the leaf with the given `index` grants to `recipient`.
+        ///
+        /// The `proof` contains the hex encoded sibling hashes on the path from the leaf to the root, separated by commas.
+        /// Anyone may submit a claim, the tokens are always deposited to the `recipient` account.
+        pub fn claim(&mut self, index: u64, recipient: Address, amount: Decimal, proof: String) {
+            assert!(Context::current_epoch() <= self.expiry_epoch, "The airdrop has expired");
+            assert!(!self.is_claimed(index), "The tokens have already been claimed");
+
+            let mut node = Self::hash(&Self::leaf(index, recipient, amount));
+            for sibling in proof.split(',').filter(|s| !s.is_empty()) {
+                node = Self::hash_pair(&node, &Self::decode_hash(sibling));
+            }
+            assert!(node == self.merkle_root, "Invalid Merkle proof");
+
+            // Mark the leaf as claimed before sending out the tokens
+            let word = self.claimed.get(&(index / 128)).unwrap_or(0);
+            self.claimed.insert(index / 128, word | (1u128 << (index % 128)));
+
+            Account::from(recipient).deposit(self.tokens.take(amount));
+        }
+
+        /// Returns all tokens that have not been claimed before the expiry epoch.
+        #[auth(admin_badge)]
+        pub fn reclaim_unclaimed(&mut self) -> Bucket {
+            assert!(Context::current_epoch() > self.expiry_epoch, "The airdrop has not expired yet");
+
+            self.tokens.take_all()
+        }
+
+        // Encodes a leaf as the index (8 bytes, big-endian), followed by the bytes of the recipient address
+        // and the raw amount in atto units (16 bytes, big-endian two's complement).
+        // Unlike the display format of Decimal, this encoding does not depend on trailing zeros or precision.
+        fn leaf(index: u64, recipient: Address, amount: Decimal) -> Vec<u8> {
+            let mut leaf = index.to_be_bytes().to_vec();
+            leaf.extend(recipient.to_vec());
+            leaf.extend(amount.0.to_be_bytes());
+            leaf
+        }
+
+        // Calculates the SHA-256 hash of the given `data`
+        fn hash(data: &[u8]) -> [u8; 32] {
+            let mut hasher = Sha256::new();
+            hasher.update(data);
+            hasher.finalize().into()
+        }
+
+        // Calculates the hash of an inner node from its two children
+        fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+            let mut hasher = Sha256::new();
+            if a <= b {
+                hasher.update(a);
+                hasher.update(b);
+            } else {
+                hasher.update(b);
+                hasher.update(a);
+            }
+            hasher.finalize().into()
+        }
+
+        // Decodes a hex encoded SHA-256 hash
+        fn decode_hash(hex: &str) -> [u8; 32] {
+            assert!(hex.len() == 64 && hex.is_ascii(), "A hash must consist of 64 hex characters");
+
+            let mut hash = [0u8; 32];
+            for (i, byte) in hash.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).expect("Invalid hex character in hash");
+            }
+            hash
+        }
+    }
+}
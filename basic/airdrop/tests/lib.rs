@@ -1,6 +1,7 @@
 use radix_engine::ledger::*;
 use radix_engine::transaction::*;
 use scrypto::prelude::*;
+use sha2::{Digest, Sha256};
 
 #[test]
 fn test_airdrop_0_users() {
@@ -30,7 +31,7 @@ fn test_airdrop_1_user() {
     assert_eq!(test_env.get_balance(test_env.admin_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000000").unwrap());
     assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000000").unwrap());
 
-    test_env.add_recipient(user1_account);
+    test_env.add_recipient(user1_account, "1");
 
     let receipt = test_env.perform_airdrop(1000, RADIX_TOKEN);
     println!("{:?}", receipt);
@@ -55,9 +56,9 @@ fn test_airdrop_3_users() {
     assert_eq!(test_env.get_balance(user2_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000000").unwrap());
     assert_eq!(test_env.get_balance(user3_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000000").unwrap());
 
-    test_env.add_recipient(user1_account);
-    test_env.add_recipient(user2_account);
-    test_env.add_recipient(user3_account);
+    test_env.add_recipient(user1_account, "1");
+    test_env.add_recipient(user2_account, "1");
+    test_env.add_recipient(user3_account, "1");
 
     let receipt = test_env.perform_airdrop(1000, RADIX_TOKEN);
     assert!(receipt.success);
@@ -68,12 +69,247 @@ fn test_airdrop_3_users() {
     assert_eq!(test_env.get_balance(user3_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000333.333333333333333334").unwrap());
 }
 
+#[test]
+fn test_airdrop_weighted_users() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+
+    // Set up environment.
+    let mut test_env = TestEnv::new(&mut ledger);
+    let (_, user1_account) = test_env.new_account();
+    let (_, user2_account) = test_env.new_account();
+    let (_, user3_account) = test_env.new_account();
+
+    test_env.add_recipient(user1_account, "1");
+    test_env.add_recipient(user2_account, "2");
+    test_env.add_recipient(user3_account, "3");
+
+    let receipt = test_env.perform_airdrop(1000, RADIX_TOKEN);
+    assert!(receipt.success);
+
+    let user1_balance = test_env.get_balance(user1_account, RADIX_TOKEN).unwrap();
+    let user2_balance = test_env.get_balance(user2_account, RADIX_TOKEN).unwrap();
+    let user3_balance = test_env.get_balance(user3_account, RADIX_TOKEN).unwrap();
+
+    assert_eq!(test_env.get_balance(test_env.admin_account, RADIX_TOKEN).unwrap(), Decimal::from_str("999000").unwrap());
+    assert_eq!(user1_balance, Decimal::from_str("1000166.666666666666666666").unwrap());
+    assert_eq!(user2_balance, Decimal::from_str("1000333.333333333333333333").unwrap());
+    assert_eq!(user3_balance, Decimal::from_str("1000500.000000000000000001").unwrap());
+
+    // The rounding dust ends up with the last recipient, so no tokens are lost
+    let received = user1_balance + user2_balance + user3_balance - Decimal::from_str("3000000").unwrap();
+    assert_eq!(received, Decimal::from_str("1000").unwrap());
+}
+
+#[test]
+fn test_airdrop_zero_weight() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+
+    // Set up environment.
+    let mut test_env = TestEnv::new(&mut ledger);
+    let (_, user1_account) = test_env.new_account();
+
+    let receipt = test_env.try_add_recipient(user1_account, "0");
+    assert!(!receipt.success);
+    let log_message = &receipt.logs.get(0).unwrap().1;
+    assert!(log_message.starts_with("Panicked at 'The weight of a recipient must be positive'"));
+}
+
+#[test]
+fn test_claim_airdrop() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+
+    // Set up environment.
+    let mut test_env = TestEnv::new(&mut ledger);
+    let (user1_key, user1_account) = test_env.new_account();
+    let (_, user2_account) = test_env.new_account();
+
+    test_env.add_recipient(user1_account, "1");
+    test_env.add_recipient(user2_account, "3");
+
+    let receipt = test_env.perform_claim_airdrop(1000, RADIX_TOKEN, 10);
+    assert!(receipt.success);
+
+    // The tokens are only reserved, the recipients have to claim them
+    assert_eq!(test_env.get_balance(test_env.admin_account, RADIX_TOKEN).unwrap(), Decimal::from_str("999000").unwrap());
+    assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000000").unwrap());
+    assert_eq!(test_env.get_balance(user2_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000000").unwrap());
+
+    // A ticket can only be issued once
+    let receipt = test_env.issue_ticket(user1_account);
+    assert!(receipt.success);
+    let receipt = test_env.issue_ticket(user1_account);
+    assert!(!receipt.success);
+    let log_message = &receipt.logs.get(0).unwrap().1;
+    assert!(log_message.starts_with("Panicked at 'The recipient has no claim ticket to issue'"));
+
+    let receipt = test_env.claim(user1_account, user1_key);
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000250").unwrap());
+
+    // A ticket can only be redeemed once
+    let receipt = test_env.claim(user1_account, user1_key);
+    assert!(!receipt.success);
+
+    // The unclaimed tokens cannot be reclaimed before the claim airdrop expires
+    let receipt = test_env.reclaim_unclaimed();
+    assert!(!receipt.success);
+
+    test_env.advance_epoch(11);
+
+    let receipt = test_env.reclaim_unclaimed();
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(test_env.admin_account, RADIX_TOKEN).unwrap(), Decimal::from_str("999750").unwrap());
+}
+
+#[test]
+fn test_claim_airdrop_expired() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+
+    // Set up environment.
+    let mut test_env = TestEnv::new(&mut ledger);
+    let (user1_key, user1_account) = test_env.new_account();
+
+    test_env.add_recipient(user1_account, "1");
+
+    let receipt = test_env.perform_claim_airdrop(1000, RADIX_TOKEN, 10);
+    assert!(receipt.success);
+    let receipt = test_env.issue_ticket(user1_account);
+    assert!(receipt.success);
+
+    test_env.advance_epoch(11);
+
+    let receipt = test_env.claim(user1_account, user1_key);
+    assert!(!receipt.success);
+    let log_message = &receipt.logs.get(0).unwrap().1;
+    assert!(log_message.starts_with("Panicked at 'The claim ticket has expired'"));
+    assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000000").unwrap());
+}
+
+#[test]
+fn test_merkle_airdrop() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+
+    // Set up environment.
+    let mut test_env = TestEnv::new(&mut ledger);
+    let (_, user1_account) = test_env.new_account();
+    let (_, user2_account) = test_env.new_account();
+
+    // Build a Merkle tree with two leaves
+    let leaf1 = merkle_leaf(0, user1_account, "300");
+    let leaf2 = merkle_leaf(1, user2_account, "700");
+    let root = merkle_hash_pair(&leaf1, &leaf2);
+
+    let (component, _) = test_env.new_merkle_airdrop(1000, RADIX_TOKEN, &to_hex(&root), 10);
+
+    // The amount is hashed as raw atto units, so its notation doesn't matter
+    let receipt = test_env.merkle_claim(component, 0, user1_account, "300.00", &to_hex(&leaf2));
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000300").unwrap());
+
+    // A leaf can only be claimed once
+    let receipt = test_env.merkle_claim(component, 0, user1_account, "300", &to_hex(&leaf2));
+    assert!(!receipt.success);
+    let log_message = &receipt.logs.get(0).unwrap().1;
+    assert!(log_message.starts_with("Panicked at 'The tokens have already been claimed'"));
+
+    // The amount must match the leaf
+    let receipt = test_env.merkle_claim(component, 1, user2_account, "800", &to_hex(&leaf1));
+    assert!(!receipt.success);
+    let log_message = &receipt.logs.get(0).unwrap().1;
+    assert!(log_message.starts_with("Panicked at 'Invalid Merkle proof'"));
+
+    let receipt = test_env.merkle_claim(component, 1, user2_account, "700", &to_hex(&leaf1));
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(user2_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000700").unwrap());
+}
+
+#[test]
+fn test_vesting_airdrop_linear() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+
+    // Set up environment.
+    let mut test_env = TestEnv::new(&mut ledger);
+    let (user1_key, user1_account) = test_env.new_account();
+
+    test_env.add_recipient(user1_account, "1");
+
+    let receipt = test_env.perform_vesting_airdrop(1000, RADIX_TOKEN, 0, 10, 1);
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(test_env.admin_account, RADIX_TOKEN).unwrap(), Decimal::from_str("999000").unwrap());
+
+    test_env.advance_epoch(4);
+    let receipt = test_env.claim_vested(user1_account, user1_key);
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000400").unwrap());
+
+    // Nothing new has vested in the same epoch
+    let receipt = test_env.claim_vested(user1_account, user1_key);
+    assert!(!receipt.success);
+
+    test_env.advance_epoch(20);
+    let receipt = test_env.claim_vested(user1_account, user1_key);
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1001000").unwrap());
+}
+
+#[test]
+fn test_vesting_airdrop_cliffs() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+
+    // Set up environment.
+    let mut test_env = TestEnv::new(&mut ledger);
+    let (user1_key, user1_account) = test_env.new_account();
+
+    test_env.add_recipient(user1_account, "1");
+
+    let receipt = test_env.perform_vesting_airdrop(1000, RADIX_TOKEN, 0, 10, 5);
+    assert!(receipt.success);
+
+    // The first cliff is at epoch 5
+    test_env.advance_epoch(4);
+    let receipt = test_env.claim_vested(user1_account, user1_key);
+    assert!(!receipt.success);
+    let log_message = &receipt.logs.get(0).unwrap().1;
+    assert!(log_message.starts_with("Panicked at 'No tokens have vested since the last claim'"));
+
+    test_env.advance_epoch(3);
+    let receipt = test_env.claim_vested(user1_account, user1_key);
+    assert!(receipt.success);
+    assert_eq!(test_env.get_balance(user1_account, RADIX_TOKEN).unwrap(), Decimal::from_str("1000500").unwrap());
+}
+
+fn merkle_leaf(index: u64, address: Address, amount: &str) -> [u8; 32] {
+    let mut leaf = index.to_be_bytes().to_vec();
+    leaf.extend(address.to_vec());
+    leaf.extend(Decimal::from_str(amount).unwrap().0.to_be_bytes());
+    Sha256::digest(&leaf).into()
+}
+
+fn merkle_hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    if a <= b {
+        hasher.update(a);
+        hasher.update(b);
+    } else {
+        hasher.update(b);
+        hasher.update(a);
+    }
+    hasher.finalize().into()
+}
+
+fn to_hex(hash: &[u8; 32]) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 struct TestEnv<'a> {
     executor: TransactionExecutor<'a, InMemoryLedger>,
+    package: Address,
     admin_key: Address,
     admin_account: Address,
     component: Address,
-    admin_badge: Address
+    admin_badge: Address,
+    claim_ticket: Address,
+    vesting_ticket: Address
 }
 
 impl<'a> TestEnv<'a> {
@@ -95,13 +331,18 @@ impl<'a> TestEnv<'a> {
         assert!(receipt.success);
 
         let admin_badge = receipt.resource_def(0).unwrap();
+        let claim_ticket = receipt.resource_def(2).unwrap();
+        let vesting_ticket = receipt.resource_def(3).unwrap();
 
         Self {
             executor,
+            package,
             admin_key,
             admin_account,
             component: receipt.component(0).unwrap(),
-            admin_badge
+            admin_badge,
+            claim_ticket,
+            vesting_ticket
         }
     }
 
@@ -110,13 +351,23 @@ impl<'a> TestEnv<'a> {
         (key, self.executor.new_account(key))
     }
 
-    pub fn add_recipient(&mut self, recipient: Address) {
+    pub fn advance_epoch(&mut self, amount: u64) {
+        self.executor.set_current_epoch(self.executor.current_epoch() + amount);
+    }
+
+    pub fn add_recipient(&mut self, recipient: Address, weight: &str) {
+        let receipt = self.try_add_recipient(recipient, weight);
+        assert!(receipt.success);
+    }
+
+    fn try_add_recipient(&mut self, recipient: Address, weight: &str) -> Receipt {
         let tx = TransactionBuilder::new(&self.executor)
             .call_method(
                 self.component,
                 "add_recipient",
                 vec![
                     format!("{}", recipient),
+                    weight.to_string(),
                     format!("1,{}", self.admin_badge)
                 ],
                 Some(self.admin_account),
@@ -127,7 +378,7 @@ impl<'a> TestEnv<'a> {
             .unwrap();
         let receipt = self.executor.run(tx, false).unwrap();
         println!("{:?}\n", receipt);
-        assert!(receipt.success);
+        receipt
     }
 
     fn perform_airdrop(&mut self, amount: i32, token: Address) -> Receipt {
@@ -150,6 +401,160 @@ impl<'a> TestEnv<'a> {
         receipt
     }
 
+    fn perform_claim_airdrop(&mut self, amount: i32, token: Address, expiry_epoch: u64) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(
+                self.component,
+                "perform_claim_airdrop",
+                vec![
+                    format!("{},{}", amount, token),
+                    expiry_epoch.to_string(),
+                    format!("1,{}", self.admin_badge)
+                ],
+                Some(self.admin_account),
+            )
+            .deposit_all_buckets(self.admin_account)
+            .drop_all_bucket_refs()
+            .build(vec![self.admin_key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    fn issue_ticket(&mut self, recipient: Address) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(
+                self.component,
+                "issue_ticket",
+                vec![format!("{}", recipient)],
+                None,
+            )
+            .build(vec![self.admin_key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    fn claim(&mut self, account: Address, key: Address) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(
+                self.component,
+                "claim",
+                vec![format!("1,{}", self.claim_ticket)],
+                Some(account),
+            )
+            .deposit_all_buckets(account)
+            .drop_all_bucket_refs()
+            .build(vec![key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    fn reclaim_unclaimed(&mut self) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(
+                self.component,
+                "reclaim_unclaimed",
+                vec![format!("1,{}", self.admin_badge)],
+                Some(self.admin_account),
+            )
+            .deposit_all_buckets(self.admin_account)
+            .drop_all_bucket_refs()
+            .build(vec![self.admin_key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    fn perform_vesting_airdrop(&mut self, amount: i32, token: Address, start_epoch: u64, end_epoch: u64, step_epochs: u64) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(
+                self.component,
+                "perform_vesting_airdrop",
+                vec![
+                    format!("{},{}", amount, token),
+                    start_epoch.to_string(),
+                    end_epoch.to_string(),
+                    step_epochs.to_string(),
+                    format!("1,{}", self.admin_badge)
+                ],
+                Some(self.admin_account),
+            )
+            .deposit_all_buckets(self.admin_account)
+            .drop_all_bucket_refs()
+            .build(vec![self.admin_key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    fn claim_vested(&mut self, account: Address, key: Address) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(
+                self.component,
+                "claim_vested",
+                vec![format!("1,{}", self.vesting_ticket)],
+                Some(account),
+            )
+            .deposit_all_buckets(account)
+            .drop_all_bucket_refs()
+            .build(vec![key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    fn new_merkle_airdrop(&mut self, amount: i32, token: Address, merkle_root: &str, expiry_epoch: u64) -> (Address, Address) {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_function(
+                self.package,
+                "MerkleAirdrop",
+                "new",
+                vec![
+                    format!("{},{}", amount, token),
+                    merkle_root.to_string(),
+                    expiry_epoch.to_string()
+                ],
+                Some(self.admin_account),
+            )
+            .deposit_all_buckets(self.admin_account)
+            .drop_all_bucket_refs()
+            .build(vec![self.admin_key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        assert!(receipt.success);
+
+        (receipt.component(0).unwrap(), receipt.resource_def(0).unwrap())
+    }
+
+    fn merkle_claim(&mut self, component: Address, index: u64, recipient: Address, amount: &str, proof: &str) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(
+                component,
+                "claim",
+                vec![
+                    index.to_string(),
+                    format!("{}", recipient),
+                    amount.to_string(),
+                    proof.to_string()
+                ],
+                None,
+            )
+            .build(vec![self.admin_key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
     fn get_balance(&self, account: Address, token: Address) -> Result<Decimal, String> {
         let ledger = self.executor.ledger();
         let account_component = ledger.get_component(account).unwrap();
@@ -0,0 +1,235 @@
+use sbor::*;
+use scrypto::prelude::*;
+
+use crate::mint_badge;
+
+#[derive(TypeId, Encode, Decode, Clone)]
+struct Order {
+    bidder: ResourceDef,
+    quantity: Decimal,
+    max_price: Decimal,
+}
+
+#[derive(TypeId, Encode, Decode)]
+struct BatchBidder {
+    // the payment escrowed for all orders of the bidder
+    escrowed: Decimal,
+    // the quantity of the offering allocated to the bidder at settlement
+    allocation: Decimal,
+    // the amount the bidder pays for the allocation, set at settlement
+    cost: Decimal,
+    claimed: bool,
+}
+
+blueprint! {
+    struct BatchAuction {
+        component_address: Address,
+
+        offering: Vault,
+        escrow: Vault,
+        // the quantity of the offering that is auctioned
+        supply: Decimal,
+
+        start: u64,
+        duration: u64,
+        payment_resource: ResourceDef,
+        reserve_price: Decimal,
+
+        bidders: LazyMap<ResourceDef, BatchBidder>,
+        orders: Vec<Order>,
+
+        settled: bool,
+        clearing_price: Decimal,
+        sold: Decimal,
+        // the sum of the costs of all bidders, which is paid to the auctioneer
+        payment_total: Decimal,
+
+        auctioneer_badge: ResourceDef,
+        payment_claimed: bool,
+    }
+
+    impl BatchAuction {
+        pub fn new(offering: Bucket, duration: u64, payment_resource: Address, reserve_price: Decimal) -> (Component, Bucket) {
+            assert!(offering.amount() > Decimal::zero(), "Incorrect offering");
+
+            assert!(reserve_price > Decimal::zero(), "Reserve price must be positive");
+
+            // instantiate a batch auction component
+            let auction = Self {
+                component_address: Address::Component([0; 26]),
+
+                supply: offering.amount(),
+                offering: Vault::with_bucket(offering),
+                escrow: Vault::new(payment_resource.clone()),
+
+                start: Context::current_epoch(),
+                duration,
+                payment_resource: ResourceDef::from(payment_resource),
+                reserve_price,
+
+                bidders: LazyMap::new(),
+                orders: Vec::new(),
+
+                settled: false,
+                clearing_price: Decimal::zero(),
+                sold: Decimal::zero(),
+                payment_total: Decimal::zero(),
+
+                auctioneer_badge: ResourceDef::from(Address::ResourceDef([0; 26])),
+                payment_claimed: false,
+            }
+            .instantiate();
+
+            // save the component address
+            let mut auction_state = auction.get_state::<Self>();
+            auction_state.component_address = auction.address();
+
+            // mint the auctioneer badge
+            let auctioneer_badge = mint_badge("Auctioneer badge", auction_state.component_address);
+
+            // save the auctioneer badge resource definition
+            auction_state.auctioneer_badge = auctioneer_badge.resource_def();
+            auction.put_state(auction_state);
+
+            (auction, auctioneer_badge)
+        }
+
+        pub fn register(&mut self) -> Bucket {
+            // check if the auction is open
+            assert!(Context::current_epoch() <= self.start + self.duration, "Auction closed");
+
+            // mint a bidder badge
+            let bidder_badge = mint_badge("Bidder badge", self.component_address);
+
+            // save the bidder using the bidder badge resource definition
+            self.bidders.insert(bidder_badge.resource_def(), BatchBidder { escrowed: Decimal::zero(), allocation: Decimal::zero(), cost: Decimal::zero(), claimed: false });
+
+            bidder_badge
+        }
+
+        pub fn place_order(&mut self, quantity: Decimal, max_price: Decimal, payment: Bucket, bidder_badge: BucketRef) -> Bucket {
+            // check if the auction is open
+            assert!(Context::current_epoch() <= self.start + self.duration, "Auction closed");
+
+            // check the bidder badge and get the bidder
+            let bidder_id = bidder_badge.resource_def();
+            let mut bidder = self.get_bidder(bidder_badge);
+
+            // check the order
+            assert!(quantity > Decimal::zero(), "Quantity must be positive");
+            assert!(max_price >= self.reserve_price, "Max price lower than the reserve price");
+
+            // check the payment, the order must be backed by the full amount
+            let amount = quantity * max_price;
+            assert!(payment.resource_def() == self.payment_resource, "Incorrect payment token");
+            assert!(payment.amount() >= amount, "Payment lower than quantity times max price");
+
+            // escrow the payment and save the order
+            self.escrow.put(payment.take(amount));
+            bidder.escrowed += amount;
+            self.bidders.insert(bidder_id.clone(), bidder);
+            self.orders.push(Order { bidder: bidder_id, quantity, max_price });
+
+            // return the change
+            payment
+        }
+
+        pub fn settle(&mut self) {
+            // check if the auction is closed
+            assert!(Context::current_epoch() > self.start + self.duration, "Auction open");
+
+            // check if the auction has not been settled yet
+            assert!(!self.settled, "Auction already settled");
+
+            // fill the orders from the highest to the lowest max price, orders with the same max price are filled in the order they were placed
+            let mut orders = self.orders.clone();
+            orders.sort_by(|a, b| b.max_price.partial_cmp(&a.max_price).unwrap());
+
+            for order in orders {
+                let remaining = self.supply - self.sold;
+                if remaining == Decimal::zero() {
+                    break;
+                }
+
+                let filled = if order.quantity < remaining { order.quantity } else { remaining };
+                let mut bidder = self.bidders.get(&order.bidder).unwrap();
+                bidder.allocation += filled;
+                self.bidders.insert(order.bidder, bidder);
+
+                // the max price of the last filled order is the uniform clearing price
+                self.sold += filled;
+                self.clearing_price = order.max_price;
+            }
+
+            // every bidder pays the clearing price for their allocation, the costs are rounded per bidder,
+            // so the auctioneer is paid their sum rather than the sold quantity times the clearing price.
+            // The escrow of each order is rounded down separately, so a cost is capped at what the bidder escrowed
+            let mut bidder_ids: Vec<ResourceDef> = Vec::new();
+            for order in &self.orders {
+                if !bidder_ids.contains(&order.bidder) {
+                    bidder_ids.push(order.bidder.clone());
+                }
+            }
+            for bidder_id in bidder_ids {
+                let mut bidder = self.bidders.get(&bidder_id).unwrap();
+                let cost = bidder.allocation * self.clearing_price;
+                bidder.cost = if cost < bidder.escrowed { cost } else { bidder.escrowed };
+                self.payment_total += bidder.cost;
+                self.bidders.insert(bidder_id, bidder);
+            }
+
+            self.settled = true;
+        }
+
+        pub fn claim(&mut self, bidder_badge: BucketRef) -> (Bucket, Bucket) {
+            // settle the auction if nobody has done it yet
+            if !self.settled {
+                self.settle();
+            }
+
+            // check the bidder badge and get the bidder
+            let bidder_id = bidder_badge.resource_def();
+            let mut bidder = self.get_bidder(bidder_badge);
+
+            // check if the bidder has not yet claimed
+            assert!(!bidder.claimed, "Allocation already claimed");
+
+            // the bidder pays for the allocation and gets the rest of the escrowed payment back
+            let refund = bidder.escrowed - bidder.cost;
+            let allocation = bidder.allocation;
+
+            // save that the bidder claimed
+            bidder.claimed = true;
+            self.bidders.insert(bidder_id, bidder);
+
+            (self.offering.take(allocation), self.escrow.take(refund))
+        }
+
+        #[auth(auctioneer_badge)]
+        pub fn claim_payment(&mut self) -> (Bucket, Bucket) {
+            // settle the auction if nobody has done it yet
+            if !self.settled {
+                self.settle();
+            }
+
+            // check if the payment has not been yet claimed
+            assert!(!self.payment_claimed, "Payment already claimed");
+
+            // save that the auctioneer has claimed the payment
+            self.payment_claimed = true;
+
+            // take the payment for the sold quantity and the unsold part of the offering
+            let payment = self.escrow.take(self.payment_total);
+            (payment, self.offering.take(self.supply - self.sold))
+        }
+
+        fn get_bidder(&self, bidder_badge: BucketRef) -> BatchBidder {
+            assert!(bidder_badge.amount() > Decimal::zero(), "No bidder badge presented");
+            let bidder = self.bidders.get(&bidder_badge.resource_def());
+            assert!(bidder.is_some(), "Incorrect bidder badge");
+            bidder_badge.drop();
+
+            bidder.unwrap()
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use scrypto::prelude::*;
+
+use crate::mint_badge;
+
+blueprint! {
+    struct DutchAuction {
+        component_address: Address,
+
+        offering: Vault,
+        payment: Vault,
+
+        start: u64,
+        duration: u64,
+        // the price decreases once every `step` epochs, a step of 1 is a linear decrease
+        step: u64,
+        payment_resource: ResourceDef,
+        start_price: Decimal,
+        floor_price: Decimal,
+
+        auctioneer_badge: ResourceDef,
+    }
+
+    impl DutchAuction {
+        pub fn new(offering: Bucket, duration: u64, step: u64, payment_resource: Address, start_price: Decimal, floor_price: Decimal) -> (Component, Bucket) {
+            assert!(offering.amount() > Decimal::zero(), "Incorrect offering");
+
+            assert!(duration > 0, "Duration must be at least one epoch");
+            assert!(step > 0 && step <= duration, "Step must be between one epoch and the duration");
+            assert!(floor_price > Decimal::zero(), "Floor price must be positive");
+            assert!(start_price >= floor_price, "Start price lower than the floor price");
+
+            // instantiate a dutch auction component
+            let auction = Self {
+                component_address: Address::Component([0; 26]),
+
+                offering: Vault::with_bucket(offering),
+                payment: Vault::new(payment_resource.clone()),
+
+                start: Context::current_epoch(),
+                duration,
+                step,
+                payment_resource: ResourceDef::from(payment_resource),
+                start_price,
+                floor_price,
+
+                auctioneer_badge: ResourceDef::from(Address::ResourceDef([0; 26])),
+            }
+            .instantiate();
+
+            // save the component address
+            let mut auction_state = auction.get_state::<Self>();
+            auction_state.component_address = auction.address();
+
+            // mint the auctioneer badge
+            let auctioneer_badge = mint_badge("Auctioneer badge", auction_state.component_address);
+
+            // save the auctioneer badge resource definition
+            auction_state.auctioneer_badge = auctioneer_badge.resource_def();
+            auction.put_state(auction_state);
+
+            (auction, auctioneer_badge)
+        }
+
+        pub fn current_price(&self) -> Decimal {
+            // the price has reached the floor at the end of the auction
+            let elapsed = Context::current_epoch() - self.start;
+            if elapsed >= self.duration {
+                return self.floor_price;
+            }
+
+            // only count whole steps
+            let elapsed_steps = elapsed - elapsed % self.step;
+            self.start_price - (self.start_price - self.floor_price) * Decimal::from(elapsed_steps) / Decimal::from(self.duration)
+        }
+
+        pub fn buy(&mut self, payment: Bucket) -> (Bucket, Bucket) {
+            // check if the auction is open
+            assert!(Context::current_epoch() <= self.start + self.duration, "Auction closed");
+
+            // check if the offering hasn't yet been sold
+            assert!(!self.offering.is_empty(), "Offering already sold");
+
+            // check the payment
+            let price = self.current_price();
+            assert!(payment.resource_def() == self.payment_resource, "Incorrect payment token");
+            assert!(payment.amount() >= price, "Payment lower than the current price");
+
+            // take the payment and return the offering and the change
+            self.payment.put(payment.take(price));
+
+            (self.offering.take_all(), payment)
+        }
+
+        #[auth(auctioneer_badge)]
+        pub fn claim_payment(&mut self) -> (Bucket, Bucket) {
+            // check if the offering has been sold or the auction is closed
+            assert!(
+                self.offering.is_empty() ||
+                Context::current_epoch() > self.start + self.duration,
+                "Offering not sold and auction open");
+
+            // take the payment and the offering (in case it has not been sold)
+            (self.payment.take_all(), self.offering.take_all())
+        }
+    }
+}
@@ -1,12 +1,28 @@
+mod batch_auction;
+mod dutch_auction;
+mod sealed_bid_auction;
+
 use sbor::*;
 use scrypto::prelude::*;
 
 const PAYMENT_DEADLINE: u64 = 100;
 
+// Mints a badge that identifies the auctioneer or a bidder of the auction with the given address
+fn mint_badge(name: &str, auction: Address) -> Bucket {
+    ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+        .metadata("name", name)
+        .metadata("auction", auction.to_string())
+        .initial_supply_fungible(1)
+}
+
 #[derive(TypeId, Encode, Decode)]
 struct Bidder {
     bid: Decimal,
     bid_bond_reclaimed: bool,
+    // collateralized auctions only: the amount currently escrowed for the bid
+    escrowed: Decimal,
+    // collateralized auctions only: the amount of outbid bids that can be withdrawn
+    refund: Decimal,
 }
 
 blueprint! {
@@ -16,26 +32,39 @@ blueprint! {
         offering: Vault,
         bid_bonds: Vault,
         payment: Vault,
+        // collateralized auctions only: holds the full amount of every bid
+        escrow: Vault,
 
-        start: u64,
-        duration: u64,
+        // the end of the auction, it is pushed back by bids placed shortly before it
+        end: u64,
         payment_resource: ResourceDef,
         reserve_price: Decimal,
         bid_bond: Decimal,
+        // the minimum amount by which a bid must exceed the highest bid, in percent of the highest bid if `min_increment_percent` is set
+        min_increment: Decimal,
+        min_increment_percent: bool,
+        // a bid placed less than `extension_window` epochs before the end pushes the end back by `extension` epochs
+        extension_window: u64,
+        extension: u64,
         
         bidders: LazyMap<ResourceDef, Bidder>,
         highest_bid: Decimal,
+        highest_bidder: Option<ResourceDef>,
+        // if set, every bid must be backed by the full amount
+        collateralized: bool,
         
         auctioneer_badge: ResourceDef,
         payment_claimed: bool,
     }
 
     impl Auction {
-        pub fn new(offering: Bucket, duration: u64, payment_resource: Address, reserve_price: Decimal, bid_bond: Decimal) -> (Component, Bucket) {
+        pub fn new(offering: Bucket, duration: u64, payment_resource: Address, reserve_price: Decimal, bid_bond: Decimal, collateralized: bool, min_increment: Decimal, min_increment_percent: bool, extension_window: u64, extension: u64) -> (Component, Bucket) {
             assert!(offering.amount() > Decimal::zero(), "Incorrect offering");
             
             assert!(bid_bond <= reserve_price, "Bid bond higher than the reserve price");
 
+            assert!(min_increment >= Decimal::zero(), "Negative minimum increment");
+
             // instantiate an auction component
             let auction = Self {
                 component_address: Address::Component([0; 26]),
@@ -43,32 +72,35 @@ blueprint! {
                 offering: Vault::with_bucket(offering),
                 bid_bonds: Vault::new(payment_resource.clone()),
                 payment: Vault::new(payment_resource.clone()),
+                escrow: Vault::new(payment_resource.clone()),
 
-                start: Context::current_epoch(),
-                duration,
+                end: Context::current_epoch() + duration,
                 payment_resource: ResourceDef::from(payment_resource),
                 reserve_price,
                 bid_bond,
+                min_increment,
+                min_increment_percent,
+                extension_window,
+                extension,
 
                 bidders: LazyMap::new(),
                 highest_bid: Decimal::zero(),
+                highest_bidder: None,
+                collateralized,
 
                 auctioneer_badge: ResourceDef::from(Address::ResourceDef([0; 26])),
                 payment_claimed: false,
             }
             .instantiate();
 
-            // save the component address (it doesn't seem like there is a way to get it later from iside a method)
+            // save the component address (it doesn't seem like there is a way to get it later from inside a method)
             let mut auction_state = auction.get_state::<Self>();
             auction_state.component_address = auction.address();
 
             // mint the auctioneer badge
-            let auctioneer_badge = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
-                .metadata("name", "Acutioneer badge")
-                .metadata("auction", auction_state.component_address.to_string())
-                .initial_supply_fungible(1);
+            let auctioneer_badge = mint_badge("Auctioneer badge", auction_state.component_address);
 
-            // save the auctioner badge resource definition
+            // save the auctioneer badge resource definition
             auction_state.auctioneer_badge = auctioneer_badge.resource_def();
             auction.put_state(auction_state);
 
@@ -77,7 +109,7 @@ blueprint! {
 
         pub fn register(&mut self, bid_bond: Bucket) -> Bucket {
             // check if the auction is open
-            assert!(Context::current_epoch() <= self.start + self.duration, "Auction closed");
+            assert!(Context::current_epoch() <= self.end, "Auction closed");
 
             // check the bid bond
             assert!(bid_bond.resource_def() == self.payment_resource, "Incorrect payment token");
@@ -87,41 +119,115 @@ blueprint! {
             self.bid_bonds.put(bid_bond);
 
             // mint a bidder badge
-            let bidder_badge = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
-                .metadata("name", "Bidder badge")
-                .metadata("auction", self.component_address.to_string())
-                .initial_supply_fungible(1);
+            let bidder_badge = mint_badge("Bidder badge", self.component_address);
 
             // save the bidder using the bidder badge resource definition
-            self.bidders.insert(bidder_badge.resource_def(), Bidder { bid: Decimal::zero(), bid_bond_reclaimed: false });
+            self.bidders.insert(bidder_badge.resource_def(), Bidder { bid: Decimal::zero(), bid_bond_reclaimed: false, escrowed: Decimal::zero(), refund: Decimal::zero() });
 
             bidder_badge
         }
 
         pub fn bid(&mut self, bid: Decimal, bidder_badge: BucketRef) {
+            // bids in a collateralized auction must come with the payment
+            assert!(!self.collateralized, "Collateralized auction, use bid_with_payment");
+
             // check if the auction is open
-            assert!(Context::current_epoch() <= self.start + self.duration, "Auction closed");
+            assert!(Context::current_epoch() <= self.end, "Auction closed");
 
             // check the bidder badge and get the bidder
             let bidder_id = bidder_badge.resource_def();
             let mut bidder = self.get_bidder(bidder_badge);
 
             // check the bid
-            assert!(bid >= self.reserve_price, "Bid lower than the reserve price");
-            assert!(bid > self.highest_bid, "Bid not higer than the current highest bid");
+            self.check_bid(bid);
 
             // save the bid
             bidder.bid = bid;
-            self.bidders.insert(bidder_id, bidder);
+            self.bidders.insert(bidder_id.clone(), bidder);
             self.highest_bid = bid;
+            self.highest_bidder = Some(bidder_id);
+            self.extend_end();
+        }
+
+        pub fn bid_with_payment(&mut self, payment: Bucket, bidder_badge: BucketRef) {
+            // check if it is a collateralized auction
+            assert!(self.collateralized, "Auction not collateralized, use bid");
+
+            // check if the auction is open
+            assert!(Context::current_epoch() <= self.end, "Auction closed");
+
+            // check the bidder badge
+            let bidder_id = bidder_badge.resource_def();
+            self.get_bidder(bidder_badge);
+
+            // check the payment, it is the bid
+            assert!(payment.resource_def() == self.payment_resource, "Incorrect payment token");
+            let bid = payment.amount();
+            self.check_bid(bid);
+
+            // the previous highest bidder can withdraw their escrowed bid
+            if let Some(previous_bidder_id) = self.highest_bidder.clone() {
+                let mut previous_bidder = self.bidders.get(&previous_bidder_id).unwrap();
+                previous_bidder.refund += previous_bidder.escrowed;
+                previous_bidder.escrowed = Decimal::zero();
+                self.bidders.insert(previous_bidder_id, previous_bidder);
+            }
+
+            // escrow and save the bid (reloading the bidder as they might have been the previous highest bidder)
+            self.escrow.put(payment);
+            let mut bidder = self.bidders.get(&bidder_id).unwrap();
+            bidder.bid = bid;
+            bidder.escrowed = bid;
+            self.bidders.insert(bidder_id.clone(), bidder);
+            self.highest_bid = bid;
+            self.highest_bidder = Some(bidder_id);
+            self.extend_end();
+        }
+
+        pub fn withdraw_refund(&mut self, bidder_badge: BucketRef) -> Bucket {
+            // check the bidder badge and get the bidder
+            let bidder_id = bidder_badge.resource_def();
+            let mut bidder = self.get_bidder(bidder_badge);
+
+            // check if the bidder has been outbid
+            assert!(bidder.refund > Decimal::zero(), "Nothing to refund");
+
+            // save that the bidder withdrew the refund
+            let refund = bidder.refund;
+            bidder.refund = Decimal::zero();
+            self.bidders.insert(bidder_id, bidder);
+
+            self.escrow.take(refund)
+        }
+
+        pub fn collect_offering(&mut self, bidder_badge: BucketRef) -> Bucket {
+            // check if it is a collateralized auction
+            assert!(self.collateralized, "Auction not collateralized, use claim_offering");
+
+            // check if the auction is closed
+            assert!(Context::current_epoch() > self.end, "Auction open");
+
+            // check the bidder badge and get the bidder
+            let bidder = self.get_bidder(bidder_badge);
+
+            // check if it is the winning bidder, the winning bid has already been escrowed
+            assert!(bidder.bid > Decimal::zero() && bidder.bid == self.highest_bid, "Not the winning bidder");
+
+            // check if the offering hasn't yet been claimed
+            assert!(!self.offering.is_empty(), "Offering already claimed");
+
+            self.offering.take_all()
         }
 
         pub fn claim_offering(&mut self, payment: Bucket, bidder_badge: BucketRef) -> Bucket {
+            // the winner of a collateralized auction has already paid
+            assert!(!self.collateralized, "Collateralized auction, use collect_offering");
+
             // check if the auction is closed
-            assert!(Context::current_epoch() > self.start + self.duration, "Auction open");
+            assert!(Context::current_epoch() > self.end, "Auction open");
 
             // check if the payment deadline has not passed yet
-            assert!(Context::current_epoch() <= self.start + self.duration + PAYMENT_DEADLINE, "Payment deadline passed");
+            assert!(Context::current_epoch() <= self.end + PAYMENT_DEADLINE, "Payment deadline passed");
 
             // check the bidder badge and get the bidder
             let bidder = self.get_bidder(bidder_badge);
@@ -144,14 +250,14 @@ blueprint! {
 
         pub fn reclaim_bid_bond(&mut self, bidder_badge: BucketRef) -> Bucket {
             // check if auction is closed
-            assert!(Context::current_epoch() > self.start + self.duration, "Acution open");
+            assert!(Context::current_epoch() > self.end, "Auction open");
 
             // check bidder badge and get the bidder
             let bidder_id = bidder_badge.resource_def();
             let mut bidder = self.get_bidder(bidder_badge);
 
-            // check if it is not the winning bid
-            assert!(bidder.bid == Decimal::zero() || bidder.bid != self.highest_bid, "Winning bidder cannot reclaim the bid bond");
+            // check if it is not the winning bid (the winner of a collateralized auction has already paid)
+            assert!(self.collateralized || bidder.bid == Decimal::zero() || bidder.bid != self.highest_bid, "Winning bidder cannot reclaim the bid bond");
 
             // check if the bidder has not yet reclaimed the bid bond
             assert!(!bidder.bid_bond_reclaimed, "Bid bond already reclaimed");
@@ -166,28 +272,60 @@ blueprint! {
         #[auth(auctioneer_badge)]
         pub fn claim_payment(&mut self) -> (Bucket, Bucket) {
             // check if auction is closed
-            assert!(Context::current_epoch() > self.start + self.duration, "Auction open");
+            assert!(Context::current_epoch() > self.end, "Auction open");
 
             // check if the payment has not been yet claimed
             assert!(!self.payment_claimed, "Payment already claimed");
 
+            // the winning bid of a collateralized auction is paid out of the escrow
+            if self.collateralized && self.highest_bid > Decimal::zero() {
+                self.payment.put(self.escrow.take(self.highest_bid));
+            }
+
             // check if there were no bids or the payment has been received or the payment deadline has passed
             assert!(
                 self.highest_bid == Decimal::zero() ||
                 !self.payment.is_empty() || 
-                Context::current_epoch() > self.start + self.duration + PAYMENT_DEADLINE,
+                Context::current_epoch() > self.end + PAYMENT_DEADLINE,
                 "Payment not received and the payment deadline not passed");
 
             // if the offering has been sold then add the winner's bid bond to the payment
-            if self.highest_bid > Decimal::zero() {
+            if self.highest_bid > Decimal::zero() && !self.collateralized {
                 self.payment.put(self.bid_bonds.take(self.bid_bond));
             }
 
-            // save that the auctionner has claimed the payment
+            // save that the auctioneer has claimed the payment
             self.payment_claimed = true;
 
-            // take the payment and the offering (in case there there were no bidders or the payment has not been received)
-            (self.payment.take_all(), self.offering.take_all())            
+            // the offering of a collateralized auction with bids belongs to the winner
+            if self.collateralized && self.highest_bid > Decimal::zero() {
+                return (self.payment.take_all(), Bucket::new(self.offering.resource_def()));
+            }
+
+            // take the payment and the offering (in case there were no bidders or the payment has not been received)
+            (self.payment.take_all(), self.offering.take_all())
+        }
+
+        fn check_bid(&self, bid: Decimal) {
+            assert!(bid >= self.reserve_price, "Bid lower than the reserve price");
+            assert!(bid > self.highest_bid, "Bid not higher than the current highest bid");
+
+            // the minimum increment only applies once there is a bid to outbid
+            if self.highest_bid > Decimal::zero() {
+                let increment = if self.min_increment_percent {
+                    self.highest_bid * self.min_increment / 100
+                } else {
+                    self.min_increment
+                };
+                assert!(bid >= self.highest_bid + increment, "Bid increment lower than the minimum increment");
+            }
+        }
+
+        fn extend_end(&mut self) {
+            // push back the end if the bid was placed shortly before it, so that no one can snipe the auction
+            if self.end - Context::current_epoch() < self.extension_window {
+                self.end += self.extension;
+            }
         }
 
         fn get_bidder(&self, bidder_badge: BucketRef) -> Bidder {
@@ -0,0 +1,290 @@
+use sbor::*;
+use scrypto::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::{mint_badge, PAYMENT_DEADLINE};
+
+#[derive(TypeId, Encode, Decode)]
+struct SealedBidder {
+    // hex encoded SHA-256 hash of the bid and the salt, see `commitment`
+    commitment: Option<String>,
+    revealed: bool,
+    bid: Decimal,
+    bid_bond_reclaimed: bool,
+}
+
+blueprint! {
+    struct SealedBidAuction {
+        component_address: Address,
+
+        offering: Vault,
+        bid_bonds: Vault,
+        payment: Vault,
+
+        start: u64,
+        bidding_duration: u64,
+        reveal_duration: u64,
+        payment_resource: ResourceDef,
+        reserve_price: Decimal,
+        bid_bond: Decimal,
+        // if set, the winner pays the second highest bid (Vickrey auction), otherwise their own bid
+        second_price: bool,
+
+        bidders: LazyMap<ResourceDef, SealedBidder>,
+        highest_bid: Decimal,
+        // the first bidder to reveal the highest bid, they win if another bidder revealed the same bid
+        highest_bidder: Option<ResourceDef>,
+        second_highest_bid: Decimal,
+        // number of bidders who committed a bid but have not revealed it
+        unrevealed: u64,
+
+        auctioneer_badge: ResourceDef,
+        payment_claimed: bool,
+    }
+
+    impl SealedBidAuction {
+        pub fn new(offering: Bucket, bidding_duration: u64, reveal_duration: u64, payment_resource: Address, reserve_price: Decimal, bid_bond: Decimal, second_price: bool) -> (Component, Bucket) {
+            assert!(offering.amount() > Decimal::zero(), "Incorrect offering");
+
+            assert!(bid_bond <= reserve_price, "Bid bond higher than the reserve price");
+
+            assert!(reveal_duration > 0, "Reveal phase must be at least one epoch");
+
+            // instantiate a sealed bid auction component
+            let auction = Self {
+                component_address: Address::Component([0; 26]),
+
+                offering: Vault::with_bucket(offering),
+                bid_bonds: Vault::new(payment_resource.clone()),
+                payment: Vault::new(payment_resource.clone()),
+
+                start: Context::current_epoch(),
+                bidding_duration,
+                reveal_duration,
+                payment_resource: ResourceDef::from(payment_resource),
+                reserve_price,
+                bid_bond,
+                second_price,
+
+                bidders: LazyMap::new(),
+                highest_bid: Decimal::zero(),
+                highest_bidder: None,
+                second_highest_bid: Decimal::zero(),
+                unrevealed: 0,
+
+                auctioneer_badge: ResourceDef::from(Address::ResourceDef([0; 26])),
+                payment_claimed: false,
+            }
+            .instantiate();
+
+            // save the component address
+            let mut auction_state = auction.get_state::<Self>();
+            auction_state.component_address = auction.address();
+
+            // mint the auctioneer badge
+            let auctioneer_badge = mint_badge("Auctioneer badge", auction_state.component_address);
+
+            // save the auctioneer badge resource definition
+            auction_state.auctioneer_badge = auctioneer_badge.resource_def();
+            auction.put_state(auction_state);
+
+            (auction, auctioneer_badge)
+        }
+
+        pub fn register(&mut self, bid_bond: Bucket) -> Bucket {
+            // check if the bidding phase is open
+            assert!(Context::current_epoch() <= self.bidding_end(), "Bidding closed");
+
+            // check the bid bond
+            assert!(bid_bond.resource_def() == self.payment_resource, "Incorrect payment token");
+            assert!(bid_bond.amount() == self.bid_bond, "Incorrect bid bond");
+
+            // take the bid bond
+            self.bid_bonds.put(bid_bond);
+
+            // mint a bidder badge
+            let bidder_badge = mint_badge("Bidder badge", self.component_address);
+
+            // save the bidder using the bidder badge resource definition
+            self.bidders.insert(bidder_badge.resource_def(), SealedBidder { commitment: None, revealed: false, bid: Decimal::zero(), bid_bond_reclaimed: false });
+
+            bidder_badge
+        }
+
+        pub fn commit_bid(&mut self, commitment: String, bidder_badge: BucketRef) {
+            // check if the bidding phase is open
+            assert!(Context::current_epoch() <= self.bidding_end(), "Bidding closed");
+
+            // check the commitment
+            assert!(commitment.len() == 64, "Commitment must be a hex encoded SHA-256 hash");
+
+            // check the bidder badge and get the bidder
+            let bidder_id = bidder_badge.resource_def();
+            let mut bidder = self.get_bidder(bidder_badge);
+
+            // save the commitment, a bidder may replace their commitment during the bidding phase
+            if bidder.commitment.is_none() {
+                self.unrevealed += 1;
+            }
+            bidder.commitment = Some(commitment.to_lowercase());
+            self.bidders.insert(bidder_id, bidder);
+        }
+
+        pub fn reveal_bid(&mut self, bid: Decimal, salt: String, bidder_badge: BucketRef) {
+            // check if the reveal phase is open
+            assert!(Context::current_epoch() > self.bidding_end(), "Bidding phase not over");
+            assert!(Context::current_epoch() <= self.reveal_end(), "Reveal phase closed");
+
+            // check the bidder badge and get the bidder
+            let bidder_id = bidder_badge.resource_def();
+            let mut bidder = self.get_bidder(bidder_badge);
+
+            // check the bid against the commitment
+            assert!(!bidder.revealed, "Bid already revealed");
+            let commitment = bidder.commitment.clone();
+            assert!(commitment.is_some(), "No bid committed");
+            assert!(commitment.unwrap() == Self::commitment(bid, &salt), "Bid and salt do not match the commitment");
+
+            // save the revealed bid, bids lower than the reserve price do not take part in the auction
+            bidder.revealed = true;
+            self.unrevealed -= 1;
+            if bid >= self.reserve_price {
+                bidder.bid = bid;
+                // on a tie the first revealed bid wins, the tied bid becomes the second highest bid
+                if bid > self.highest_bid {
+                    self.second_highest_bid = self.highest_bid;
+                    self.highest_bid = bid;
+                    self.highest_bidder = Some(bidder_id.clone());
+                } else if bid > self.second_highest_bid {
+                    self.second_highest_bid = bid;
+                }
+            }
+            self.bidders.insert(bidder_id, bidder);
+        }
+
+        pub fn claim_offering(&mut self, payment: Bucket, bidder_badge: BucketRef) -> Bucket {
+            // check if the reveal phase is closed
+            assert!(Context::current_epoch() > self.reveal_end(), "Auction open");
+
+            // check if the payment deadline has not passed yet
+            assert!(Context::current_epoch() <= self.reveal_end() + PAYMENT_DEADLINE, "Payment deadline passed");
+
+            // check the bidder badge
+            let bidder_id = bidder_badge.resource_def();
+            self.get_bidder(bidder_badge);
+
+            // check if it is the winning bidder
+            assert!(self.is_winner(&bidder_id), "Not the winning bidder");
+
+            // check if the offering hasn't yet been claimed
+            assert!(!self.offering.is_empty(), "Offering already claimed");
+
+            // check the payment
+            assert!(payment.resource_def() == self.payment_resource, "Incorrect payment token");
+            assert!(payment.amount() == self.price() - self.bid_bond, "Incorrect payment amount");
+
+            // take the payment and return the offering
+            self.payment.put(payment);
+
+            self.offering.take_all()
+        }
+
+        pub fn reclaim_bid_bond(&mut self, bidder_badge: BucketRef) -> Bucket {
+            // check if the reveal phase is closed
+            assert!(Context::current_epoch() > self.reveal_end(), "Auction open");
+
+            // check bidder badge and get the bidder
+            let bidder_id = bidder_badge.resource_def();
+            let mut bidder = self.get_bidder(bidder_badge);
+
+            // check if the bidder revealed their bid, unrevealed bid bonds go to the auctioneer
+            assert!(bidder.commitment.is_none() || bidder.revealed, "Bid not revealed, the bid bond is lost");
+
+            // check if it is not the winning bid
+            assert!(!self.is_winner(&bidder_id), "Winning bidder cannot reclaim the bid bond");
+
+            // check if the bidder has not yet reclaimed the bid bond
+            assert!(!bidder.bid_bond_reclaimed, "Bid bond already reclaimed");
+
+            // save that the bidder reclaimed the bid bond
+            bidder.bid_bond_reclaimed = true;
+            self.bidders.insert(bidder_id, bidder);
+
+            self.bid_bonds.take(self.bid_bond)
+        }
+
+        #[auth(auctioneer_badge)]
+        pub fn claim_payment(&mut self) -> (Bucket, Bucket) {
+            // check if the reveal phase is closed
+            assert!(Context::current_epoch() > self.reveal_end(), "Auction open");
+
+            // check if the payment has not been yet claimed
+            assert!(!self.payment_claimed, "Payment already claimed");
+
+            // check if there were no bids or the payment has been received or the payment deadline has passed
+            assert!(
+                self.highest_bid == Decimal::zero() ||
+                !self.payment.is_empty() ||
+                Context::current_epoch() > self.reveal_end() + PAYMENT_DEADLINE,
+                "Payment not received and the payment deadline not passed");
+
+            // if the offering has been sold then add the winner's bid bond to the payment
+            if self.highest_bid > Decimal::zero() {
+                self.payment.put(self.bid_bonds.take(self.bid_bond));
+            }
+
+            // the bid bonds of bidders who did not reveal their bids are lost
+            self.payment.put(self.bid_bonds.take(self.bid_bond * Decimal::from(self.unrevealed)));
+
+            // save that the auctioneer has claimed the payment
+            self.payment_claimed = true;
+
+            // take the payment and the offering (in case there were no bidders or the payment has not been received)
+            (self.payment.take_all(), self.offering.take_all())
+        }
+
+        fn bidding_end(&self) -> u64 {
+            self.start + self.bidding_duration
+        }
+
+        fn reveal_end(&self) -> u64 {
+            self.bidding_end() + self.reveal_duration
+        }
+
+        // the price the winner has to pay
+        fn price(&self) -> Decimal {
+            if !self.second_price {
+                return self.highest_bid;
+            }
+
+            // the winner pays the second highest bid, but never less than the reserve price
+            if self.second_highest_bid > self.reserve_price {
+                self.second_highest_bid
+            } else {
+                self.reserve_price
+            }
+        }
+
+        fn is_winner(&self, bidder_id: &ResourceDef) -> bool {
+            self.highest_bidder.as_ref() == Some(bidder_id)
+        }
+
+        // the hex encoded SHA-256 hash of the bid in atto units (the raw i128 of the decimal, 16 bytes big-endian)
+        // followed by the UTF-8 bytes of the salt, so that the commitment doesn't depend on how the bid is written
+        fn commitment(bid: Decimal, salt: &str) -> String {
+            let mut hasher = Sha256::new();
+            hasher.update(bid.0.to_be_bytes());
+            hasher.update(salt.as_bytes());
+            hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+
+        fn get_bidder(&self, bidder_badge: BucketRef) -> SealedBidder {
+            assert!(bidder_badge.amount() > Decimal::zero(), "No bidder badge presented");
+            let bidder = self.bidders.get(&bidder_badge.resource_def());
+            assert!(bidder.is_some(), "Incorrect bidder badge");
+            bidder_badge.drop();
+
+            bidder.unwrap()
+        }
+    }
+}
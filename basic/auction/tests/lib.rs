@@ -0,0 +1,621 @@
+use radix_engine::ledger::*;
+use radix_engine::transaction::*;
+use scrypto::prelude::*;
+use sha2::{Digest, Sha256};
+
+#[test]
+fn test_collateralized_auction() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_auction(auctioneer, offering, true, "0", false, 0, 0);
+    let bidder1_badge = test_env.register(bidder1, auction);
+    let bidder2_badge = test_env.register(bidder2, auction);
+
+    // A collateralized auction only accepts bids that come with the payment
+    let receipt = test_env.call_method(bidder1, auction, "bid", vec!["150".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+
+    // Every bid is escrowed in full
+    let receipt = test_env.call_method(bidder1, auction, "bid_with_payment", vec![format!("150,{}", RADIX_TOKEN), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder1.account, RADIX_TOKEN, "999840");
+
+    // The highest bidder has nothing to withdraw
+    let receipt = test_env.call_method(bidder1, auction, "withdraw_refund", vec![format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+
+    let receipt = test_env.call_method(bidder2, auction, "bid_with_payment", vec![format!("200,{}", RADIX_TOKEN), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+
+    // Once outbid, the bid can be withdrawn, but only once
+    let receipt = test_env.call_method(bidder1, auction, "withdraw_refund", vec![format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder1.account, RADIX_TOKEN, "999990");
+    let receipt = test_env.call_method(bidder1, auction, "withdraw_refund", vec![format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+
+    // The offering can't be collected before the auction has ended
+    let receipt = test_env.call_method(bidder2, auction, "collect_offering", vec![format!("1,{}", bidder2_badge)]);
+    assert!(!receipt.success);
+
+    test_env.set_current_epoch(11);
+
+    // Only the winner collects the offering, without paying again
+    let receipt = test_env.call_method(bidder1, auction, "collect_offering", vec![format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "collect_offering", vec![format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder2.account, offering, "1");
+    test_env.assert_balance(bidder2.account, RADIX_TOKEN, "999790");
+
+    // Every bidder, including the winner, gets their bid bond back
+    let receipt = test_env.call_method(bidder1, auction, "reclaim_bid_bond", vec![format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder1.account, RADIX_TOKEN, "1000000");
+    let receipt = test_env.call_method(bidder2, auction, "reclaim_bid_bond", vec![format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder2.account, RADIX_TOKEN, "999800");
+
+    // The auctioneer receives the winning bid out of the escrow
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000200");
+    test_env.assert_balance(auctioneer.account, offering, "0");
+}
+
+#[test]
+fn test_minimum_increment() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, _) = test_env.new_auction(auctioneer, offering, false, "10", false, 0, 0);
+    let bidder1_badge = test_env.register(bidder1, auction);
+    let bidder2_badge = test_env.register(bidder2, auction);
+
+    // The first bid only has to reach the reserve price
+    let receipt = test_env.call_method(bidder1, auction, "bid", vec!["100".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+
+    // Later bids must exceed the highest bid by at least 10
+    let receipt = test_env.call_method(bidder2, auction, "bid", vec!["109".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "bid", vec!["110".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+}
+
+#[test]
+fn test_minimum_increment_percent() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, _) = test_env.new_auction(auctioneer, offering, false, "5", true, 0, 0);
+    let bidder1_badge = test_env.register(bidder1, auction);
+    let bidder2_badge = test_env.register(bidder2, auction);
+
+    let receipt = test_env.call_method(bidder1, auction, "bid", vec!["200".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+
+    // Later bids must exceed the highest bid by at least 5%
+    let receipt = test_env.call_method(bidder2, auction, "bid", vec!["209".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "bid", vec!["210".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+}
+
+#[test]
+fn test_anti_sniping_extension() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+
+    // Bids less than 3 epochs before the end push the end back by 5 epochs
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_auction(auctioneer, offering, false, "0", false, 3, 5);
+    let bidder1_badge = test_env.register(bidder1, auction);
+
+    // A bid outside of the extension window doesn't change the end
+    test_env.set_current_epoch(7);
+    let receipt = test_env.call_method(bidder1, auction, "bid", vec!["100".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+
+    // The late bid at epoch 8 moves the end from epoch 10 to epoch 15
+    test_env.set_current_epoch(8);
+    let bidder2_badge = test_env.register(bidder2, auction);
+    let receipt = test_env.call_method(bidder2, auction, "bid", vec!["150".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+
+    // Registering, bidding and claiming all respect the extended end
+    test_env.set_current_epoch(12);
+    let receipt = test_env.call_method(bidder2, auction, "claim_offering", vec![format!("140,{}", RADIX_TOKEN), format!("1,{}", bidder2_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder1, auction, "bid", vec!["160".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+
+    test_env.set_current_epoch(16);
+    let receipt = test_env.call_method(bidder1, auction, "bid", vec!["170".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder1, auction, "claim_offering", vec![format!("150,{}", RADIX_TOKEN), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder1.account, offering, "1");
+
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000160");
+}
+
+#[test]
+fn test_dutch_auction() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let buyer1 = test_env.new_user();
+    let buyer2 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_dutch_auction(auctioneer, offering, 1);
+
+    // Nothing to claim while the offering is for sale
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(!receipt.success);
+
+    // The price decreases linearly from 1000 to 100 over 10 epochs, so it is 550 at epoch 5
+    test_env.set_current_epoch(5);
+    let receipt = test_env.call_method(buyer1, auction, "buy", vec![format!("500,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+
+    // The first buyer paying the current price wins and gets the change back
+    let receipt = test_env.call_method(buyer1, auction, "buy", vec![format!("600,{}", RADIX_TOKEN)]);
+    assert!(receipt.success);
+    test_env.assert_balance(buyer1.account, offering, "1");
+    test_env.assert_balance(buyer1.account, RADIX_TOKEN, "999450");
+
+    let receipt = test_env.call_method(buyer2, auction, "buy", vec![format!("600,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+    test_env.assert_balance(buyer2.account, RADIX_TOKEN, "1000000");
+
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000550");
+}
+
+#[test]
+fn test_dutch_auction_steps_and_unsold_offering() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let buyer = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_dutch_auction(auctioneer, offering, 5);
+
+    // With steps of 5 epochs the price only drops at epoch 5
+    test_env.set_current_epoch(4);
+    let receipt = test_env.call_method(buyer, auction, "buy", vec![format!("999,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+
+    // Nobody can buy after the end of the auction, the auctioneer gets the offering back
+    test_env.set_current_epoch(11);
+    let receipt = test_env.call_method(buyer, auction, "buy", vec![format!("1000,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, offering, "1");
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000000");
+}
+
+#[test]
+fn test_sealed_bid_auction_tie() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_sealed_bid_auction(auctioneer, offering, false);
+    let bidder1_badge = test_env.register(bidder1, auction);
+    let bidder2_badge = test_env.register(bidder2, auction);
+
+    let receipt = test_env.call_method(bidder1, auction, "commit_bid", vec![commitment("150", "salt1"), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "commit_bid", vec![commitment("150", "salt2"), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+
+    // Bids can't be revealed during the bidding phase
+    let receipt = test_env.call_method(bidder2, auction, "reveal_bid", vec!["150".to_string(), "salt2".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(!receipt.success);
+
+    // Bidder 2 reveals the same bid first, the notation of the bid doesn't matter
+    test_env.set_current_epoch(11);
+    let receipt = test_env.call_method(bidder2, auction, "reveal_bid", vec!["150.0".to_string(), "salt2".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(bidder1, auction, "reveal_bid", vec!["150".to_string(), "salt2".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder1, auction, "reveal_bid", vec!["150".to_string(), "salt1".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+
+    // Only the first revealer of the highest bid wins
+    test_env.set_current_epoch(16);
+    let receipt = test_env.call_method(bidder1, auction, "claim_offering", vec![format!("140,{}", RADIX_TOKEN), format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "claim_offering", vec![format!("140,{}", RADIX_TOKEN), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder2.account, offering, "1");
+    test_env.assert_balance(bidder2.account, RADIX_TOKEN, "999850");
+
+    // The other bidder gets their bid bond back, the winner doesn't
+    let receipt = test_env.call_method(bidder1, auction, "reclaim_bid_bond", vec![format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder1.account, RADIX_TOKEN, "1000000");
+    let receipt = test_env.call_method(bidder2, auction, "reclaim_bid_bond", vec![format!("1,{}", bidder2_badge)]);
+    assert!(!receipt.success);
+
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000150");
+}
+
+#[test]
+fn test_sealed_bid_auction_second_price() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+    let bidder3 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_sealed_bid_auction(auctioneer, offering, true);
+    let bidder1_badge = test_env.register(bidder1, auction);
+    let bidder2_badge = test_env.register(bidder2, auction);
+    let bidder3_badge = test_env.register(bidder3, auction);
+
+    test_env.call_method(bidder1, auction, "commit_bid", vec![commitment("150", "salt1"), format!("1,{}", bidder1_badge)]);
+    test_env.call_method(bidder2, auction, "commit_bid", vec![commitment("200", "salt2"), format!("1,{}", bidder2_badge)]);
+    test_env.call_method(bidder3, auction, "commit_bid", vec![commitment("300", "salt3"), format!("1,{}", bidder3_badge)]);
+
+    // Bidder 3 never reveals their bid
+    test_env.set_current_epoch(11);
+    let receipt = test_env.call_method(bidder1, auction, "reveal_bid", vec!["150".to_string(), "salt1".to_string(), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "reveal_bid", vec!["200".to_string(), "salt2".to_string(), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+
+    // The winner pays the second highest bid minus the bid bond
+    test_env.set_current_epoch(16);
+    let receipt = test_env.call_method(bidder2, auction, "claim_offering", vec![format!("190,{}", RADIX_TOKEN), format!("1,{}", bidder2_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "claim_offering", vec![format!("140,{}", RADIX_TOKEN), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder2.account, offering, "1");
+    test_env.assert_balance(bidder2.account, RADIX_TOKEN, "999850");
+
+    // The bid bond of the unrevealed bid is lost to the auctioneer
+    let receipt = test_env.call_method(bidder3, auction, "reclaim_bid_bond", vec![format!("1,{}", bidder3_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000160");
+}
+
+#[test]
+fn test_batch_auction() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+    let bidder3 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "10");
+    let (auction, auctioneer_badge) = test_env.new_batch_auction(auctioneer, offering, "1");
+    let bidder1_badge = test_env.register_batch(bidder1, auction);
+    let bidder2_badge = test_env.register_batch(bidder2, auction);
+    let bidder3_badge = test_env.register_batch(bidder3, auction);
+
+    // Every order must be backed by its quantity times its max price
+    let receipt = test_env.call_method(bidder1, auction, "place_order", vec!["6".to_string(), "3".to_string(), format!("17,{}", RADIX_TOKEN), format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(bidder1, auction, "place_order", vec!["6".to_string(), "3".to_string(), format!("20,{}", RADIX_TOKEN), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder1.account, RADIX_TOKEN, "999982");
+    let receipt = test_env.call_method(bidder2, auction, "place_order", vec!["6".to_string(), "2".to_string(), format!("12,{}", RADIX_TOKEN), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(bidder3, auction, "place_order", vec!["2".to_string(), "1".to_string(), format!("2,{}", RADIX_TOKEN), format!("1,{}", bidder3_badge)]);
+    assert!(receipt.success);
+
+    // The orders at 3 and 2 fill the supply of 10, so everyone pays the clearing price of 2
+    test_env.set_current_epoch(11);
+    let receipt = test_env.call_method(bidder1, auction, "claim", vec![format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder1.account, offering, "6");
+    test_env.assert_balance(bidder1.account, RADIX_TOKEN, "999988");
+    let receipt = test_env.call_method(bidder2, auction, "claim", vec![format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder2.account, offering, "4");
+    test_env.assert_balance(bidder2.account, RADIX_TOKEN, "999992");
+    let receipt = test_env.call_method(bidder3, auction, "claim", vec![format!("1,{}", bidder3_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder3.account, offering, "0");
+    test_env.assert_balance(bidder3.account, RADIX_TOKEN, "1000000");
+
+    // An allocation can only be claimed once
+    let receipt = test_env.call_method(bidder1, auction, "claim", vec![format!("1,{}", bidder1_badge)]);
+    assert!(!receipt.success);
+
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000020");
+    test_env.assert_balance(auctioneer.account, offering, "0");
+}
+
+#[test]
+fn test_batch_auction_rounding() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder1 = test_env.new_user();
+    let bidder2 = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_batch_auction(auctioneer, offering, "0.1");
+    let bidder1_badge = test_env.register_batch(bidder1, auction);
+    let bidder2_badge = test_env.register_batch(bidder2, auction);
+
+    // Half of the offering at a price of 1/3 costs 0.1666666666666666665, which is rounded down for each bidder
+    let price = "0.333333333333333333";
+    let receipt = test_env.call_method(bidder1, auction, "place_order", vec!["0.5".to_string(), price.to_string(), format!("1,{}", RADIX_TOKEN), format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "place_order", vec!["0.5".to_string(), price.to_string(), format!("1,{}", RADIX_TOKEN), format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+
+    test_env.set_current_epoch(11);
+    let receipt = test_env.call_method(bidder1, auction, "claim", vec![format!("1,{}", bidder1_badge)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(bidder2, auction, "claim", vec![format!("1,{}", bidder2_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder2.account, offering, "0.5");
+    test_env.assert_balance(bidder2.account, RADIX_TOKEN, "999999.833333333333333334");
+
+    // The auctioneer gets exactly what the bidders paid, not the sold quantity times the clearing price
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000000.333333333333333332");
+}
+
+#[test]
+fn test_batch_auction_rounding_several_orders() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let auctioneer = test_env.new_user();
+    let bidder = test_env.new_user();
+
+    let offering = test_env.create_offering(auctioneer, "1");
+    let (auction, auctioneer_badge) = test_env.new_batch_auction(auctioneer, offering, "0.1");
+    let bidder_badge = test_env.register_batch(bidder, auction);
+
+    // Each order escrows 0.166666666666666666, while the whole offering at a price of 1/3 costs 0.333333333333333333
+    let price = "0.333333333333333333";
+    for _ in 0..2 {
+        let receipt = test_env.call_method(bidder, auction, "place_order", vec!["0.5".to_string(), price.to_string(), format!("1,{}", RADIX_TOKEN), format!("1,{}", bidder_badge)]);
+        assert!(receipt.success);
+    }
+
+    // The cost is capped at the escrowed payment, so nothing is refunded
+    test_env.set_current_epoch(11);
+    let receipt = test_env.call_method(bidder, auction, "claim", vec![format!("1,{}", bidder_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(bidder.account, offering, "1");
+    test_env.assert_balance(bidder.account, RADIX_TOKEN, "999999.666666666666666668");
+
+    // The auctioneer is paid what was escrowed
+    let receipt = test_env.call_method(auctioneer, auction, "claim_payment", vec![format!("1,{}", auctioneer_badge)]);
+    assert!(receipt.success);
+    test_env.assert_balance(auctioneer.account, RADIX_TOKEN, "1000000.333333333333333332");
+}
+
+// The hex encoded commitment to the bid and the salt, as described in the README
+fn commitment(bid: &str, salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(Decimal::from_str(bid).unwrap().0.to_be_bytes());
+    hasher.update(salt.as_bytes());
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[derive(Copy, Clone)]
+struct User {
+    key: Address,
+    account: Address,
+}
+
+struct TestEnv<'a> {
+    executor: TransactionExecutor<'a, InMemoryLedger>,
+    package: Address,
+}
+
+impl<'a> TestEnv<'a> {
+    pub fn new(ledger: &'a mut InMemoryLedger) -> Self {
+        let mut executor = TransactionExecutor::new(ledger, 0, 0);
+        let package = executor.publish_package(include_code!("auction"));
+
+        Self { executor, package }
+    }
+
+    pub fn new_user(&mut self) -> User {
+        let key = self.executor.new_public_key();
+        User { key, account: self.executor.new_account(key) }
+    }
+
+    pub fn set_current_epoch(&mut self, epoch: u64) {
+        self.executor.set_current_epoch(epoch);
+    }
+
+    // Creates a token with the given supply in the account of the user
+    pub fn create_offering(&mut self, user: User, supply: &str) -> Address {
+        let tx = TransactionBuilder::new(&self.executor)
+            .new_token_fixed(HashMap::new(), Decimal::from_str(supply).unwrap())
+            .deposit_all_buckets(user.account)
+            .build(vec![user.key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        assert!(receipt.success);
+
+        receipt.resource_def(0).unwrap()
+    }
+
+    // Creates an `Auction` for the whole offering, that ends at epoch 10 and requires a reserve price of 100 and a bid bond of 10
+    pub fn new_auction(&mut self, user: User, offering: Address, collateralized: bool, min_increment: &str, min_increment_percent: bool, extension_window: u64, extension: u64) -> (Address, Address) {
+        let amount = self.get_balance(user.account, offering);
+        let receipt = self.call_function(user, "Auction", "new", vec![
+            format!("{},{}", amount, offering),
+            "10".to_string(),
+            RADIX_TOKEN.to_string(),
+            "100".to_string(),
+            "10".to_string(),
+            collateralized.to_string(),
+            min_increment.to_string(),
+            min_increment_percent.to_string(),
+            extension_window.to_string(),
+            extension.to_string()
+        ]);
+        assert!(receipt.success);
+
+        (receipt.component(0).unwrap(), receipt.resource_def(0).unwrap())
+    }
+
+    // Creates a `DutchAuction` for the whole offering, whose price decreases from 1000 to 100 until epoch 10
+    pub fn new_dutch_auction(&mut self, user: User, offering: Address, step: u64) -> (Address, Address) {
+        let amount = self.get_balance(user.account, offering);
+        let receipt = self.call_function(user, "DutchAuction", "new", vec![
+            format!("{},{}", amount, offering),
+            "10".to_string(),
+            step.to_string(),
+            RADIX_TOKEN.to_string(),
+            "1000".to_string(),
+            "100".to_string()
+        ]);
+        assert!(receipt.success);
+
+        (receipt.component(0).unwrap(), receipt.resource_def(0).unwrap())
+    }
+
+    // Creates a `SealedBidAuction` for the whole offering, whose bidding phase ends at epoch 10 and reveal phase at epoch 15,
+    // with a reserve price of 100 and a bid bond of 10
+    pub fn new_sealed_bid_auction(&mut self, user: User, offering: Address, second_price: bool) -> (Address, Address) {
+        let amount = self.get_balance(user.account, offering);
+        let receipt = self.call_function(user, "SealedBidAuction", "new", vec![
+            format!("{},{}", amount, offering),
+            "10".to_string(),
+            "5".to_string(),
+            RADIX_TOKEN.to_string(),
+            "100".to_string(),
+            "10".to_string(),
+            second_price.to_string()
+        ]);
+        assert!(receipt.success);
+
+        (receipt.component(0).unwrap(), receipt.resource_def(0).unwrap())
+    }
+
+    // Registers the user for the auction with a bid bond of 10 and returns their bidder badge
+    pub fn register(&mut self, user: User, auction: Address) -> Address {
+        let receipt = self.call_method(user, auction, "register", vec![format!("10,{}", RADIX_TOKEN)]);
+        assert!(receipt.success);
+
+        receipt.resource_def(0).unwrap()
+    }
+
+    // Creates a `BatchAuction` for the whole offering, that ends at epoch 10
+    pub fn new_batch_auction(&mut self, user: User, offering: Address, reserve_price: &str) -> (Address, Address) {
+        let amount = self.get_balance(user.account, offering);
+        let receipt = self.call_function(user, "BatchAuction", "new", vec![
+            format!("{},{}", amount, offering),
+            "10".to_string(),
+            RADIX_TOKEN.to_string(),
+            reserve_price.to_string()
+        ]);
+        assert!(receipt.success);
+
+        (receipt.component(0).unwrap(), receipt.resource_def(0).unwrap())
+    }
+
+    // Registers the user for the batch auction and returns their bidder badge
+    pub fn register_batch(&mut self, user: User, auction: Address) -> Address {
+        let receipt = self.call_method(user, auction, "register", vec![]);
+        assert!(receipt.success);
+
+        receipt.resource_def(0).unwrap()
+    }
+
+    pub fn call_function(&mut self, user: User, blueprint: &str, function: &str, args: Vec<String>) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_function(self.package, blueprint, function, args, Some(user.account))
+            .deposit_all_buckets(user.account)
+            .drop_all_bucket_refs()
+            .build(vec![user.key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    pub fn call_method(&mut self, user: User, component: Address, method: &str, args: Vec<String>) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(component, method, args, Some(user.account))
+            .deposit_all_buckets(user.account)
+            .drop_all_bucket_refs()
+            .build(vec![user.key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    pub fn assert_balance(&self, account: Address, token: Address, expected: &str) {
+        assert_eq!(self.get_balance(account, token), Decimal::from_str(expected).unwrap());
+    }
+
+    // Returns the amount of the token in the account, zero if the account has never held the token
+    fn get_balance(&self, account: Address, token: Address) -> Decimal {
+        let ledger = self.executor.ledger();
+        let account_component = ledger.get_component(account).unwrap();
+        let mut vaults = vec![];
+        let _res = radix_engine::utils::format_data_with_ledger(
+            account_component
+                .state(radix_engine::model::Actor::SuperUser)
+                .unwrap(),
+            ledger,
+            &mut vaults,
+        ).unwrap();
+
+        for vid in vaults {
+            let vault = ledger.get_vault(vid).unwrap();
+            let resource_def_address = vault
+                .resource_address(radix_engine::model::Actor::SuperUser)
+                .unwrap();
+            if token == resource_def_address {
+                return vault.amount(radix_engine::model::Actor::SuperUser).unwrap();
+            }
+        }
+
+        Decimal::zero()
+    }
+}
@@ -1,17 +1,62 @@
 use sbor::*;
 use scrypto::prelude::*;
 
+// a title in the catalogue of the library
 #[derive(TypeId, Encode, Decode)]
+struct CatalogueEntry {
+    title: String,
+    author: String,
+}
+
+// a physical copy of a book
+#[derive(NftData)]
 struct Book {
+    isbn: String,
     title: String,
     author: String,
 }
 
-#[derive(TypeId, Encode, Decode)]
-struct BorrowedBook {
+// a library membership, tracks the borrowing history of the member
+#[derive(NftData)]
+struct Member {
+    // the number of books borrowed so far
+    #[scrypto(mutable)]
+    borrow_count: u64,
+    // the IDs of the loans currently held
+    #[scrypto(mutable)]
+    loans: Vec<u128>,
+    // late fees of books returned without paying
+    #[scrypto(mutable)]
+    outstanding_fees: Decimal,
+    // the maximum number of books that can be borrowed at once
+    #[scrypto(mutable)]
+    max_loans: u32,
+}
+
+// a loan of a book copy to a member
+#[derive(NftData)]
+struct Loan {
+    // the member who borrowed the copy
+    member_id: u128,
+    // the ID of the borrowed copy
+    copy_id: u128,
+    isbn: String,
     // when the user borrowed the book
     epoch: u64,
-    user_id: Address,
+}
+
+// a place in the reservations queue of a borrowed book
+#[derive(NftData)]
+struct Reservation {
+    isbn: String,
+}
+
+#[derive(TypeId, Encode, Decode)]
+struct HeldCopy {
+    isbn: String,
+    copy_id: u128,
+    // the last epoch the reserving member can borrow the copy in
+    expiry_epoch: u64,
 }
 
 blueprint! {
@@ -19,59 +64,140 @@ blueprint! {
         // librarian (admin) badge
         librarian_badge_def: ResourceDef,
 
+        // allows the library to mint and burn books and loans
+        library_badge: Vault,
+
+        // the resource fees and deposits are paid in
+        payment_resource: ResourceDef,
         // holds the library funds, from late fees and memberships
         fees: Vault,
+        // holds the refundable membership deposits
+        deposits: Vault,
+
+        // maps ISBN numbers to the titles in the catalogue
+        catalogue: HashMap<String, CatalogueEntry>,
+
+        // every physical copy of a book is a non-fungible
+        book_def: ResourceDef,
+        // copies that are available to borrow
+        shelf: Vault,
+        // copies that are currently borrowed
+        lent: Vault,
 
-        // maps ISBN numbers to books
-        books: HashMap<String, Book>,
+        // given to members when they borrow a book
+        loan_def: ResourceDef,
 
-        // member badge
-        member_badges: Vault,
+        // given to members when they join the reservations queue of a book
+        reservation_def: ResourceDef,
+        // maps ISBN numbers to the IDs of the reservations waiting for a copy, in order
+        reservation_queues: HashMap<String, Vec<u128>>,
+        // maps reservation IDs to the copies held on the shelf for them
+        held_copies: HashMap<u128, HeldCopy>,
+        // the number of epochs a held copy is kept for the reserving member
+        reservation_epochs: u64,
+
+        // every member badge is a non-fungible
         member_badge_def: ResourceDef,
-        // membership cost in XRD
+        // the number of members allowed to join at once and the current number of members
+        max_members: u32,
+        member_count: u32,
+        // the number of books new members can borrow at once
+        max_loans: u32,
+        // membership cost in the payment resource
         membership_price: Decimal,
+        // refundable deposit paid when joining the library
+        membership_deposit: Decimal,
 
-        // maps ISBN numbers to membership badge of borrower
-        borrowed_books: HashMap<String, BorrowedBook>,
         // the number of epochs books can be borrowed for
-        borrow_epochs: u64
+        borrow_epochs: u64,
+        // the late fee charged per epoch a book is overdue, up to the cap
+        late_fee_per_epoch: Decimal,
+        late_fee_cap: Decimal
     }
 
     impl Library {
         // the library requires:
         // - member_badge_count: number of members allowed to join at once
-        // - membership_price: the cost in XRD to join the library
+        // - max_loans: the number of books a new member can borrow at once
+        // - payment_resource: the resource fees and deposits are paid in
+        // - membership_price: the cost to join the library
+        // - membership_deposit: the deposit paid when joining, refunded minus any unpaid fees when cancelling the membership
         // - borrow_epochs: the number of epochs books are borrowed for
+        // - reservation_epochs: the number of epochs a returned book is held for the next member in the reservations queue
+        // - late_fee_per_epoch: the fee charged for every epoch a book is overdue
+        // - late_fee_cap: the maximum late fee charged for a book
         // the librarian badge is returned to the caller
-        pub fn new(member_badge_count: u32, membership_price: Decimal, borrow_epochs: u64) -> (Component, Bucket) {
+        pub fn new(
+            member_badge_count: u32,
+            max_loans: u32,
+            payment_resource: Address,
+            membership_price: Decimal,
+            membership_deposit: Decimal,
+            borrow_epochs: u64,
+            reservation_epochs: u64,
+            late_fee_per_epoch: Decimal,
+            late_fee_cap: Decimal
+        ) -> (Component, Bucket) {
             let librarian_badge_bucket = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
                 .metadata("name", "Librarian Badge")
                 .metadata("symbol", "LB")
                 .initial_supply_fungible(1);
 
-            let mut books = HashMap::new();
-            let book_one = Book { author: String::from("James S. A. Corey"), title: String::from("Leviathan Wakes") };
-            let book_two = Book { author: String::from("Frank Herbert"), title: String::from("Dune") };
-            let book_three = Book { author: String::from("Dan Abnett"), title: String::from("Horus Rising") };
-            books.insert(String::from("9781611297560"), book_one);
-            books.insert(String::from("9780450011849"), book_two);
-            books.insert(String::from("9781844162949"), book_three);
+            let library_badge_bucket = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+                .metadata("name", "Library Badge")
+                .initial_supply_fungible(1);
+
+            let book_def = ResourceBuilder::new_non_fungible()
+                .metadata("name", "Library Book")
+                .flags(MINTABLE | BURNABLE)
+                .badge(library_badge_bucket.resource_def(), MAY_MINT | MAY_BURN)
+                .no_initial_supply();
 
-            let member_badges_bucket = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+            let loan_def = ResourceBuilder::new_non_fungible()
+                .metadata("name", "Library Loan")
+                .metadata("symbol", "LL")
+                .flags(MINTABLE | BURNABLE)
+                .badge(library_badge_bucket.resource_def(), MAY_MINT | MAY_BURN)
+                .no_initial_supply();
+
+            let reservation_def = ResourceBuilder::new_non_fungible()
+                .metadata("name", "Library Reservation")
+                .metadata("symbol", "LR")
+                .flags(MINTABLE | BURNABLE)
+                .badge(library_badge_bucket.resource_def(), MAY_MINT | MAY_BURN)
+                .no_initial_supply();
+
+            let member_badge_def = ResourceBuilder::new_non_fungible()
                 .metadata("name", "Library Membership Badge")
                 .metadata("symbol", "LMB")
-                .initial_supply_fungible(member_badge_count);
-            let member_badge_def = member_badges_bucket.resource_def();
+                .flags(MINTABLE | BURNABLE | INDIVIDUAL_METADATA_MUTABLE)
+                .badge(library_badge_bucket.resource_def(), MAY_MINT | MAY_BURN | MAY_CHANGE_INDIVIDUAL_METADATA)
+                .no_initial_supply();
 
             let component = Self {
                 librarian_badge_def: librarian_badge_bucket.resource_def(),
-                fees: Vault::new(RADIX_TOKEN),
-                books,
-                member_badges: Vault::with_bucket(member_badges_bucket),
+                library_badge: Vault::with_bucket(library_badge_bucket),
+                payment_resource: ResourceDef::from(payment_resource),
+                fees: Vault::new(payment_resource),
+                deposits: Vault::new(payment_resource),
+                catalogue: HashMap::new(),
+                shelf: Vault::new(book_def.address()),
+                lent: Vault::new(book_def.address()),
+                book_def,
+                loan_def,
+                reservation_def,
+                reservation_queues: HashMap::new(),
+                held_copies: HashMap::new(),
+                reservation_epochs,
                 member_badge_def,
+                max_members: member_badge_count,
+                member_count: 0,
+                max_loans,
                 membership_price,
-                borrowed_books: HashMap::new(),
-                borrow_epochs: borrow_epochs
+                membership_deposit,
+                borrow_epochs: borrow_epochs,
+                late_fee_per_epoch,
+                late_fee_cap
             }
             .instantiate();
 
@@ -81,80 +207,240 @@ blueprint! {
         // prints the state of the library
         pub fn print_library(&self) {
             info!("Current epoch, {}", {Context::current_epoch()});
-            info!("Membership price: {}, memberships available: {}", self.membership_price, self.member_badges.amount());
-            info!("All books:");
-            for (isbn, book) in &self.books {
-                info!("{}: {}, {}", isbn, book.title, book.author);
+            info!("Membership price: {}, deposit: {}, memberships available: {}", self.membership_price, self.membership_deposit, self.max_members - self.member_count);
+            info!("Books on the shelf:");
+            for copy in self.shelf.get_nfts::<Book>() {
+                let book = copy.data();
+                info!("{}: {}, {} (copy {})", book.isbn, book.title, book.author, copy.id());
             };
             info!("Borrowed books:");
-            for (isbn, borrowed_book) in &self.borrowed_books {
-                info!("{}: {}, {}", isbn, borrowed_book.user_id, borrowed_book.epoch);
+            for copy in self.lent.get_nfts::<Book>() {
+                let book = copy.data();
+                info!("{}: {}, {} (copy {})", book.isbn, book.title, book.author, copy.id());
             }
         }
 
-        // registers the account as a member of the library
-        pub fn register(&mut self, payment: Bucket) -> Bucket {
-            info!("Attempting to register user, membership badges remaining {}, payment amount {}", self.member_badges.amount(), payment.amount());
-            assert!(!self.member_badges.is_empty(), "No memberships available");
-            assert!(payment.amount() == self.membership_price, "Wrong amount sent");
-            assert!(payment.resource_def() == RADIX_TOKEN.into(), "Can only pay with XRD");
+        // registers the account as a member of the library, returns the membership badge and the change
+        pub fn register(&mut self, payment: Bucket) -> (Bucket, Bucket) {
+            info!("Attempting to register user, memberships available {}, payment amount {}", self.max_members - self.member_count, payment.amount());
+            assert!(self.member_count < self.max_members, "No memberships available");
+            assert!(payment.resource_def() == self.payment_resource, "Wrong payment resource");
+            assert!(payment.amount() >= self.membership_price + self.membership_deposit, "Not enough sent");
 
-            // take the payment
-            self.fees.put(payment);
+            // take the membership price and the deposit
+            self.fees.put(payment.take(self.membership_price));
+            self.deposits.put(payment.take(self.membership_deposit));
 
-            // return the membership badge
+            // mint the membership badge
+            self.member_count += 1;
+            let member = Member { borrow_count: 0, loans: Vec::new(), outstanding_fees: Decimal::zero(), max_loans: self.max_loans };
+            let member_badge = self.library_badge.authorize(|auth| self.member_badge_def.mint_nft(Uuid::generate(), member, auth));
             info!("Successfully registered user");
-            self.member_badges.take(1)
+            (member_badge, payment)
         }
 
-        // borrow a book from the library
+        // cancels the membership, all loans of the member must be returned with the membership badge
+        // returns the deposit minus the outstanding fees and the late fees of the returned loans
+        pub fn cancel_membership(&mut self, member_badge: Bucket, loans: Bucket) -> Bucket {
+            info!("Attempting to cancel membership");
+            assert!(member_badge.resource_def() == self.member_badge_def, "Not a membership badge");
+            assert!(member_badge.amount() == 1.into(), "Exactly one membership badge must be provided");
+            assert!(loans.is_empty() || loans.resource_def() == self.loan_def, "Not a loan");
+            let member_id = member_badge.get_nft_id();
+            let member = self.get_member(member_id);
+            assert!(loans.amount() == Decimal::from(member.loans.len() as u64), "All loans must be returned");
+
+            // return the books and add up the unpaid late fees
+            let mut unpaid_fees = member.outstanding_fees;
+            for loan in loans.get_nfts::<Loan>() {
+                let loan_data = loan.data();
+                assert!(loan_data.member_id == member_id, "Loan belongs to another member");
+                unpaid_fees += self.late_fee(&loan_data);
+                self.close_loan(loans.take_nft(loan.id()), loan_data);
+            }
+
+            // the membership becomes available again
+            self.library_badge.authorize(|auth| member_badge.burn_with_auth(auth));
+            self.member_count -= 1;
+
+            // keep the unpaid fees and refund the rest of the deposit
+            let refund = self.deposits.take(self.membership_deposit);
+            let fee = if unpaid_fees < self.membership_deposit { unpaid_fees } else { self.membership_deposit };
+            self.fees.put(refund.take(fee));
+            info!("Membership cancelled, fees deducted: {}, deposit refunded: {}", fee, refund.amount());
+            refund
+        }
+
+        // borrow a copy of a book from the library, returns the loan
         #[auth(member_badge_def)]
-        pub fn borrow_book(&mut self, isbn: String) {
+        pub fn borrow_book(&mut self, isbn: String) -> Bucket {
             info!("Attempting to borrow book with ISBN {}", isbn);
-            assert!(self.books.contains_key(&isbn), "Book not in library");
-            assert!(!self.borrowed_books.contains_key(&isbn), "Book already borrowed");
+            let member_id = auth.get_nft_id();
+            self.check_can_borrow(member_id);
+            self.expire_reservations(&isbn);
+
+            // find an available copy of the book
+            let copy_id = self.find_available_copy(&isbn);
+            assert!(copy_id.is_some(), "No copy of the book available");
 
-            let book = self.books.get(&isbn).unwrap();
-            info!("Book found (ISBN: {}, Title: {}, Author: {})", isbn, book.title, book.author);
+            self.lend(copy_id.unwrap(), member_id)
+        }
+
+        // join the reservations queue of a book that is not available, returns the reservation
+        #[auth(member_badge_def)]
+        pub fn reserve_book(&mut self, isbn: String) -> Bucket {
+            info!("Attempting to reserve book with ISBN {}", isbn);
+            assert!(self.catalogue.contains_key(&isbn), "Book not in library");
+            self.expire_reservations(&isbn);
+            assert!(self.find_available_copy(&isbn).is_none(), "Book available, borrow it instead");
 
-            // create and save a record of the book being borrowed
-            let borrowed_book = BorrowedBook { epoch: Context::current_epoch(), user_id: Self::get_user_id(&auth) };
-            self.borrowed_books.insert(isbn, borrowed_book);
-            info!("Book borrowed")
+            // queue the reservation
+            let reservation_id = Uuid::generate();
+            let reservation = Reservation { isbn: isbn.clone() };
+            let reservation_bucket = self.library_badge.authorize(|auth| self.reservation_def.mint_nft(reservation_id, reservation, auth));
+            self.reservation_queues.entry(isbn).or_insert(Vec::new()).push(reservation_id);
+            info!("Book reserved");
+            reservation_bucket
         }
 
-        // return a borrowed book to the library
+        // borrow the copy that is held for the given reservation, returns the loan
         #[auth(member_badge_def)]
-        pub fn return_book(&mut self, isbn: String) {
-            info!("Attempting to return book with ISBN {}", isbn);
-            let borrowed_book = self.get_borrowed_book(&isbn, &auth);
+        pub fn borrow_reserved_book(&mut self, reservation: Bucket) -> Bucket {
+            let (reservation_id, reservation_data) = self.get_reservation(&reservation);
+            info!("Attempting to borrow reserved book with ISBN {}", reservation_data.isbn);
+            let member_id = auth.get_nft_id();
+            self.check_can_borrow(member_id);
+            self.expire_reservations(&reservation_data.isbn);
 
-            // check the book is not overdue
-            let book_overdue = self.is_book_overdue(borrowed_book);
-            assert!(!book_overdue, "Book is overdue");
+            // check a copy is held for the reservation
+            let held_copy = self.held_copies.remove(&reservation_id);
+            assert!(held_copy.is_some(), "No copy held for this reservation");
 
-            self.borrowed_books.remove(&isbn);
+            self.library_badge.authorize(|auth| reservation.burn_with_auth(auth));
+            self.lend(held_copy.unwrap().copy_id, member_id)
+        }
+
+        // leave the reservations queue, a copy held for the reservation is passed on to the next member in the queue
+        pub fn cancel_reservation(&mut self, reservation: Bucket) {
+            let (reservation_id, reservation_data) = self.get_reservation(&reservation);
+            info!("Attempting to cancel reservation for book with ISBN {}", reservation_data.isbn);
+
+            if let Some(queue) = self.reservation_queues.get_mut(&reservation_data.isbn) {
+                queue.retain(|id| *id != reservation_id);
+            }
+            self.held_copies.remove(&reservation_id);
+            self.library_badge.authorize(|auth| reservation.burn_with_auth(auth));
+
+            self.hold_copies(&reservation_data.isbn);
+            info!("Reservation cancelled")
+        }
+
+        // return a borrowed book to the library, the late fee of an overdue book is added to the outstanding fees of the member
+        #[auth(member_badge_def)]
+        pub fn return_book(&mut self, loan: Bucket) {
+            let loan_data = self.get_loan(&loan);
+            info!("Attempting to return book with ISBN {}", loan_data.isbn);
+            assert!(loan_data.member_id == auth.get_nft_id(), "Loan belongs to another member");
+
+            // charge the member for an overdue book
+            if self.is_book_overdue(&loan_data) {
+                let fee = self.late_fee(&loan_data);
+                info!("Book is overdue, late fee {} added to outstanding fees", fee);
+                let mut member = self.get_member(loan_data.member_id);
+                member.outstanding_fees += fee;
+                self.update_member(loan_data.member_id, member);
+            }
+
+            self.close_loan(loan, loan_data);
             info!("Book returned")
         }
 
-        // pays the late fee and returhs the book to the library
+        // pays the outstanding fees of the member, returns the change
         #[auth(member_badge_def)]
-        pub fn pay_fee(&mut self, isbn: String, payment: Bucket)  {
+        pub fn pay_outstanding_fees(&mut self, payment: Bucket) -> Bucket {
+            let member_id = auth.get_nft_id();
+            let mut member = self.get_member(member_id);
+            info!("Attempting to pay outstanding fees of {} with payment amount: {}", member.outstanding_fees, payment.amount());
+            assert!(payment.resource_def() == self.payment_resource, "Wrong payment resource");
+            assert!(payment.amount() >= member.outstanding_fees, "Not enough sent");
+
+            self.fees.put(payment.take(member.outstanding_fees));
+            member.outstanding_fees = Decimal::zero();
+            self.update_member(member_id, member);
+            info!("Outstanding fees paid");
+            payment
+        }
+
+        // pays the late fee and returns the book to the library, returns the change
+        #[auth(member_badge_def)]
+        pub fn pay_fee(&mut self, loan: Bucket, payment: Bucket) -> Bucket {
             info!("Attempting to pay fee with payment amount: {}", payment.amount());
-            let borrowed_book = self.get_borrowed_book(&isbn, &auth);
+            let loan_data = self.get_loan(&loan);
+            assert!(loan_data.member_id == auth.get_nft_id(), "Loan belongs to another member");
 
             // check the book is overdue
-            let book_overdue = self.is_book_overdue(borrowed_book);
+            let book_overdue = self.is_book_overdue(&loan_data);
             assert!(book_overdue, "Book is not overdue");
 
             // check the payment is correct
-            assert!(payment.amount() == 1.into(), "Wrong amount sent");
-            assert!(payment.resource_def() == RADIX_TOKEN.into(), "Can only pay with XRD");
+            let fee = self.late_fee(&loan_data);
+            info!("Late fee: {}", fee);
+            assert!(payment.resource_def() == self.payment_resource, "Wrong payment resource");
+            assert!(payment.amount() >= fee, "Not enough sent");
+
+            // take the payment and close the loan
+            self.fees.put(payment.take(fee));
+            self.close_loan(loan, loan_data);
+            info!("Late fee paid and book returned");
+            payment
+        }
 
-            // take the payment and remove the borrowed book record
-            self.fees.put(payment);
-            self.borrowed_books.remove(&isbn);
-            info!("Late fee paid and book returned")
+        // adds a new title to the catalogue together with the given number of copies
+        #[auth(librarian_badge_def)]
+        pub fn add_book(&mut self, isbn: String, title: String, author: String, copies: u32) {
+            info!("Attempting to add book with ISBN {}", isbn);
+            assert!(!self.catalogue.contains_key(&isbn), "Book already in library");
+
+            self.catalogue.insert(isbn.clone(), CatalogueEntry { title, author });
+            self.mint_copies(&isbn, copies);
+            info!("Book added")
+        }
+
+        // adds more copies of a title that is already in the catalogue
+        #[auth(librarian_badge_def)]
+        pub fn add_copies(&mut self, isbn: String, copies: u32) {
+            info!("Attempting to add {} copies of book with ISBN {}", copies, isbn);
+            assert!(self.catalogue.contains_key(&isbn), "Book not in library");
+
+            self.mint_copies(&isbn, copies);
+            info!("Copies added")
+        }
+
+        // retires a title, copies on the shelf are destroyed right away and borrowed copies when they are returned
+        #[auth(librarian_badge_def)]
+        pub fn remove_book(&mut self, isbn: String) {
+            info!("Attempting to remove book with ISBN {}", isbn);
+            assert!(self.catalogue.contains_key(&isbn), "Book not in library");
+
+            self.catalogue.remove(&isbn);
+            self.reservation_queues.remove(&isbn);
+            self.held_copies.retain(|_, held_copy| held_copy.isbn != isbn);
+            for copy in self.shelf.get_nfts::<Book>() {
+                if copy.data().isbn == isbn {
+                    let copy_bucket = self.shelf.take_nft(copy.id());
+                    self.library_badge.authorize(|auth| copy_bucket.burn_with_auth(auth));
+                }
+            }
+            info!("Book removed")
+        }
+
+        // changes the number of books a member can borrow at once
+        #[auth(librarian_badge_def)]
+        pub fn set_max_loans(&mut self, member_id: u128, max_loans: u32) {
+            info!("Setting the maximum loans of member {} to {}", member_id, max_loans);
+            let mut member = self.get_member(member_id);
+            member.max_loans = max_loans;
+            self.update_member(member_id, member);
         }
 
         // time to get paid! withdraws all fees made by the library
@@ -164,31 +450,148 @@ blueprint! {
             self.fees.take_all()
         }
 
-        // using an ISBN and user ID returns the borrowed book
-        fn get_borrowed_book(&self, isbn: &String, badge: &BucketRef) -> &BorrowedBook {
-            // check the book is borrowed and the borrower is the current user
-            assert!(self.borrowed_books.contains_key(isbn), "Book not borrowed");
-            let borrowed_book = self.borrowed_books.get(isbn).unwrap();
-            let user_id = Self::get_user_id(&badge);
-            assert!(borrowed_book.user_id == user_id, "Book not borrowed by this user");
+        // checks the given bucket holds a single loan and returns its data
+        fn get_loan(&self, loan: &Bucket) -> Loan {
+            assert!(loan.resource_def() == self.loan_def, "Not a loan");
+            assert!(loan.amount() == 1.into(), "Exactly one loan must be returned at a time");
+            let loan_data: Loan = self.loan_def.get_nft_data(loan.get_nft_id());
+
+            let book: Book = self.book_def.get_nft_data(loan_data.copy_id);
+            info!("Book found (ISBN: {}, Title: {}, Author: {}, Copy: {})", book.isbn, book.title, book.author, loan_data.copy_id);
+            loan_data
+        }
+
+        // returns the data of the given member
+        fn get_member(&self, member_id: u128) -> Member {
+            self.member_badge_def.get_nft_data(member_id)
+        }
+
+        // stores the updated data of the given member
+        fn update_member(&self, member_id: u128, member: Member) {
+            self.library_badge.authorize(|auth| self.member_badge_def.update_nft_data(member_id, member, auth));
+        }
+
+        // checks the member has no outstanding fees and has not reached their loan limit
+        fn check_can_borrow(&self, member_id: u128) {
+            let member = self.get_member(member_id);
+            info!("Member {} has {} of {} loans, outstanding fees {}", member_id, member.loans.len(), member.max_loans, member.outstanding_fees);
+            assert!(member.outstanding_fees == Decimal::zero(), "Outstanding fees must be paid first");
+            assert!((member.loans.len() as u32) < member.max_loans, "Loan limit reached");
+        }
 
-            let book = self.books.get(isbn).unwrap();
-            info!("Book found (ISBN: {}, Title: {}, Author: {})", isbn, book.title, book.author);
-            return borrowed_book
+        // checks the given bucket holds a single reservation and returns its ID and data
+        fn get_reservation(&self, reservation: &Bucket) -> (u128, Reservation) {
+            assert!(reservation.resource_def() == self.reservation_def, "Not a reservation");
+            assert!(reservation.amount() == 1.into(), "Exactly one reservation must be provided");
+            let reservation_id = reservation.get_nft_id();
+            (reservation_id, self.reservation_def.get_nft_data(reservation_id))
         }
 
-        // returns if the given borrowed book is currently overdue
-        fn is_book_overdue(&self, borrowed_book: &BorrowedBook) -> bool {
+        // puts new copies of a title in the catalogue on the shelf
+        fn mint_copies(&mut self, isbn: &String, copies: u32) {
+            assert!(copies > 0, "At least one copy must be added");
+            let entry = self.catalogue.get(isbn).unwrap();
+
+            for _ in 0..copies {
+                let book = Book { isbn: isbn.clone(), title: entry.title.clone(), author: entry.author.clone() };
+                let copy = self.library_badge.authorize(|auth| self.book_def.mint_nft(Uuid::generate(), book, auth));
+                self.shelf.put(copy);
+            }
+
+            // the new copies may be held for members waiting in the reservations queue
+            self.hold_copies(isbn);
+        }
+
+        // moves a copy off the shelf and hands out a loan for it to the member
+        fn lend(&mut self, copy_id: u128, member_id: u128) -> Bucket {
+            let book: Book = self.book_def.get_nft_data(copy_id);
+            info!("Book found (ISBN: {}, Title: {}, Author: {}, Copy: {})", book.isbn, book.title, book.author, copy_id);
+
+            self.lent.put(self.shelf.take_nft(copy_id));
+            let loan_id = Uuid::generate();
+            let loan = Loan { member_id, copy_id, isbn: book.isbn, epoch: Context::current_epoch() };
+            let loan_bucket = self.library_badge.authorize(|auth| self.loan_def.mint_nft(loan_id, loan, auth));
+
+            // record the loan in the borrowing history of the member
+            let mut member = self.get_member(member_id);
+            member.borrow_count += 1;
+            member.loans.push(loan_id);
+            self.update_member(member_id, member);
+            info!("Book borrowed");
+            loan_bucket
+        }
+
+        // burns the loan and puts the borrowed copy back on the shelf, or destroys it if the book has been removed
+        fn close_loan(&mut self, loan: Bucket, loan_data: Loan) {
+            let loan_id = loan.get_nft_id();
+            self.library_badge.authorize(|auth| loan.burn_with_auth(auth));
+            let mut member = self.get_member(loan_data.member_id);
+            member.loans.retain(|id| *id != loan_id);
+            self.update_member(loan_data.member_id, member);
+
+            let copy = self.lent.take_nft(loan_data.copy_id);
+
+            if !self.catalogue.contains_key(&loan_data.isbn) {
+                self.library_badge.authorize(|auth| copy.burn_with_auth(auth));
+                return;
+            }
+
+            self.shelf.put(copy);
+            self.expire_reservations(&loan_data.isbn);
+        }
+
+        // returns a copy of the book on the shelf that is not held for a reservation
+        fn find_available_copy(&self, isbn: &String) -> Option<u128> {
+            self.shelf
+                .get_nfts::<Book>()
+                .into_iter()
+                .find(|copy| copy.data().isbn == *isbn && !self.held_copies.values().any(|held_copy| held_copy.copy_id == copy.id()))
+                .map(|copy| copy.id())
+        }
+
+        // releases the copies held for reservations that have not been borrowed in time and passes them on
+        fn expire_reservations(&mut self, isbn: &String) {
+            let current_epoch = Context::current_epoch();
+            self.held_copies.retain(|_, held_copy| held_copy.isbn != *isbn || held_copy.expiry_epoch >= current_epoch);
+            self.hold_copies(isbn);
+        }
+
+        // holds available copies of the book for the members at the front of the reservations queue
+        fn hold_copies(&mut self, isbn: &String) {
+            loop {
+                let waiting = self.reservation_queues.get(isbn).map_or(false, |queue| !queue.is_empty());
+                if !waiting {
+                    return;
+                }
+                let copy_id = match self.find_available_copy(isbn) {
+                    Some(copy_id) => copy_id,
+                    None => return,
+                };
+
+                let reservation_id = self.reservation_queues.get_mut(isbn).unwrap().remove(0);
+                let expiry_epoch = Context::current_epoch() + self.reservation_epochs;
+                info!("Holding copy {} for reservation {} until epoch {}", copy_id, reservation_id, expiry_epoch);
+                self.held_copies.insert(reservation_id, HeldCopy { isbn: isbn.clone(), copy_id, expiry_epoch });
+            }
+        }
+
+        // returns if the given loan is currently overdue
+        fn is_book_overdue(&self, loan: &Loan) -> bool {
             // book is overdue if current epoch is past when the book was borrowed plus the allowed borrow time
-            let book_overdue = Context::current_epoch() > borrowed_book.epoch + self.borrow_epochs;
-            info!("Book borrowed on epoch {}, current epoch {}, overdue = {}", borrowed_book.epoch, Context::current_epoch(), book_overdue);
+            let book_overdue = Context::current_epoch() > loan.epoch + self.borrow_epochs;
+            info!("Book borrowed on epoch {}, current epoch {}, overdue = {}", loan.epoch, Context::current_epoch(), book_overdue);
             return book_overdue
         }
 
-        // get user ID from the provided badge
-        fn get_user_id(badge: &BucketRef) -> Address {
-            assert!(badge.amount() > 0.into(), "Invalid badge provided");
-            return badge.resource_address();
+        // returns the late fee for the given loan, it accrues for every epoch the book is overdue up to the cap
+        fn late_fee(&self, loan: &Loan) -> Decimal {
+            let due_epoch = loan.epoch + self.borrow_epochs;
+            if Context::current_epoch() <= due_epoch {
+                return Decimal::zero();
+            }
+
+            let fee = self.late_fee_per_epoch * Decimal::from(Context::current_epoch() - due_epoch);
+            if fee < self.late_fee_cap { fee } else { self.late_fee_cap }
         }
     }
 }
@@ -2,6 +2,20 @@ use radix_engine::ledger::*;
 use radix_engine::transaction::*;
 use scrypto::prelude::*;
 
+const DUNE: &str = "9780450011849";
+
+struct TestEnv {
+    key: Address,
+    account: Address,
+    other_key: Address,
+    other_account: Address,
+    lib: Address,
+    librarian_badge_def: Address,
+    loan_def: Address,
+    reservation_def: Address,
+    member_badge_def: Address,
+}
+
 #[test]
 fn test_new() {
     let mut ledger = InMemoryLedger::with_bootstrap();
@@ -9,19 +23,7 @@ fn test_new() {
 
     let key = executor.new_public_key();
     let account = executor.new_account(key);
-    let package = executor.publish_package(include_code!("library"));
-
-    let args = vec!["10".to_string(), "1".to_string(), "3".to_string()];
-    let receipt = executor
-        .run(
-            TransactionBuilder::new(&executor)
-                .call_function(package, "Library", "new", args, None)
-                .deposit_all_buckets(account)
-                .build(vec![key])
-                .unwrap(),
-            false,
-        )
-        .unwrap();
+    let receipt = new_library(&mut executor, account, key);
     assert!(receipt.success);
 
     let components: Vec<Address> = receipt
@@ -38,82 +40,311 @@ fn test_new() {
         .filter(|a| matches!(a, Address::ResourceDef(_)))
         .map(Clone::clone)
         .collect();
-    assert_eq!(resources.len(), 2);
+    assert_eq!(resources.len(), 6);
 }
 
 #[test]
 fn test_print_library() {
     let mut ledger = InMemoryLedger::with_bootstrap();
     let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
 
-    let key = executor.new_public_key();
-    let account = executor.new_account(key);
-    let package = executor.publish_package(include_code!("library"));
-    let args = vec!["10".to_string(), "1".to_string(), "3".to_string()];
-    let receipt = executor
+    let print_library_transaction = TransactionBuilder::new(&executor)
+        .call_method(env.lib, "print_library", vec![], None)
+        .build(vec![env.key])
+        .unwrap();
+    let print_library_receipt = executor.run(print_library_transaction, false).unwrap();
+    assert!(print_library_receipt.success);
+    assert_eq!(print_library_receipt.logs.len(), 4);
+}
+
+#[test]
+fn test_register() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
+
+    let register_receipt = register(&mut executor, &env, env.account, env.key);
+    assert!(register_receipt.success);
+
+    // check print method for 1 less membership badge
+    let print_library_receipt = executor
         .run(
             TransactionBuilder::new(&executor)
-                .call_function(package, "Library", "new", args, None)
-                .deposit_all_buckets(account)
-                .build(vec![key])
+                .call_method(env.lib, "print_library", vec![], None)
+                .build(vec![env.key])
                 .unwrap(),
             false,
         )
         .unwrap();
-    let lib = receipt.component(0).unwrap();
+    assert!(print_library_receipt.success);
+    assert_eq!(
+        print_library_receipt.logs[1].1,
+        "Membership price: 1, deposit: 5, memberships available: 9"
+    );
+}
 
-    let print_library_transaction = TransactionBuilder::new(&executor)
-        .call_method(lib, "print_library", vec![], None)
-        .build(vec![key])
-        .unwrap();
-    let print_library_receipt = executor.run(print_library_transaction, false).unwrap();
+#[test]
+fn test_borrow_copies() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
+
+    // the librarian adds two copies of Dune
+    let add_receipt = add_dune(&mut executor, &env, 2);
+    assert!(add_receipt.success);
+
+    // both accounts become members and borrow a copy of Dune
+    for (account, key) in [(env.account, env.key), (env.other_account, env.other_key)] {
+        let register_receipt = register(&mut executor, &env, account, key);
+        assert!(register_receipt.success);
+
+        let borrow_receipt = borrow_dune(&mut executor, &env, account, key);
+        assert!(borrow_receipt.success);
+    }
+
+    // there are only two copies of Dune
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(!borrow_receipt.success);
+
+    // returning the loan puts the copy back on the shelf
+    let return_receipt = return_book(&mut executor, &env, env.other_account, env.other_key);
+    assert!(return_receipt.success);
+
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+}
+
+#[test]
+fn test_reservations() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
+    add_dune_and_register_both(&mut executor, &env, 1);
+
+    // the only copy is borrowed, so the other member reserves it
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+    let reserve_receipt = call_method(&mut executor, env.lib, "reserve_book", vec![DUNE.to_string(), format!("1,{}", env.member_badge_def)], env.other_account, env.other_key);
+    assert!(reserve_receipt.success);
+
+    // once returned, the copy is held for the reserving member
+    let return_receipt = return_book(&mut executor, &env, env.account, env.key);
+    assert!(return_receipt.success);
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(!borrow_receipt.success);
+
+    let borrow_receipt = borrow_reserved_book(&mut executor, &env);
+    assert!(borrow_receipt.success);
+}
+
+#[test]
+fn test_reservation_expiry() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
+    add_dune_and_register_both(&mut executor, &env, 1);
+
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+    let reserve_receipt = call_method(&mut executor, env.lib, "reserve_book", vec![DUNE.to_string(), format!("1,{}", env.member_badge_def)], env.other_account, env.other_key);
+    assert!(reserve_receipt.success);
+    let return_receipt = return_book(&mut executor, &env, env.account, env.key);
+    assert!(return_receipt.success);
+
+    // the reserving member does not borrow the book in time, so it is available again
+    executor.set_current_epoch(6);
+    let borrow_receipt = borrow_reserved_book(&mut executor, &env);
+    assert!(!borrow_receipt.success);
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+}
+
+#[test]
+fn test_late_fees() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
+
+    let add_receipt = add_dune(&mut executor, &env, 1);
+    assert!(add_receipt.success);
+
+    // the membership price and deposit must both be paid
+    let register_receipt = call_method(&mut executor, env.lib, "register", vec![format!("5,{}", RADIX_TOKEN)], env.account, env.key);
+    assert!(!register_receipt.success);
+    let register_receipt = register(&mut executor, &env, env.account, env.key);
+    assert!(register_receipt.success);
+
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+
+    // the book is due at epoch 3, two epochs late the fee is 4
+    executor.set_current_epoch(5);
+    let pay_receipt = pay_fee(&mut executor, &env, 3, env.account, env.key);
+    assert!(!pay_receipt.success);
+    let pay_receipt = pay_fee(&mut executor, &env, 4, env.account, env.key);
+    assert!(pay_receipt.success);
+
+    // the fee stops accruing at the cap of 5
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+    executor.set_current_epoch(50);
+    let pay_receipt = pay_fee(&mut executor, &env, 5, env.account, env.key);
+    assert!(pay_receipt.success);
+}
+
+#[test]
+fn test_cancel_membership() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
+
+    let add_receipt = add_dune(&mut executor, &env, 1);
+    assert!(add_receipt.success);
+    let register_receipt = register(&mut executor, &env, env.account, env.key);
+    assert!(register_receipt.success);
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+
+    // cancelling with an overdue loan returns the book and keeps the late fee from the deposit
+    executor.set_current_epoch(5);
+    let cancel_receipt = call_method(&mut executor, env.lib, "cancel_membership", vec![format!("1,{}", env.member_badge_def), format!("1,{}", env.loan_def)], env.account, env.key);
+    assert!(cancel_receipt.success);
+    assert!(cancel_receipt.logs.iter().any(|log| log.1 == "Membership cancelled, fees deducted: 4, deposit refunded: 1"));
+
+    // the membership is available again
+    let print_library_receipt = call_method(&mut executor, env.lib, "print_library", vec![], env.account, env.key);
     assert!(print_library_receipt.success);
-    assert_eq!(print_library_receipt.logs.len(), 7);
+    assert_eq!(print_library_receipt.logs[1].1, "Membership price: 1, deposit: 5, memberships available: 10");
 }
 
 #[test]
-fn test_register() {
+fn test_member_loans() {
     let mut ledger = InMemoryLedger::with_bootstrap();
     let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+    let env = setup(&mut executor);
+    add_dune_and_register_both(&mut executor, &env, 3);
 
-    let key = executor.new_public_key();
-    let account = executor.new_account(key);
-    let package = executor.publish_package(include_code!("library"));
-    let args = vec!["10".to_string(), "1".to_string(), "3".to_string()];
-    let receipt = executor
+    // a member can only borrow two books at once
+    for _ in 0..2 {
+        let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+        assert!(borrow_receipt.success);
+    }
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(!borrow_receipt.success);
+
+    // a loan can only be returned with the badge of the member who borrowed the book
+    let transfer_receipt = executor
         .run(
             TransactionBuilder::new(&executor)
+                .call_method(env.account, "withdraw", vec!["1".to_string(), format!("{}", env.loan_def)], Some(env.account))
+                .deposit_all_buckets(env.other_account)
+                .build(vec![env.key])
+                .unwrap(),
+            false,
+        )
+        .unwrap();
+    assert!(transfer_receipt.success);
+    let return_receipt = return_book(&mut executor, &env, env.other_account, env.other_key);
+    assert!(!return_receipt.success);
+
+    // nor can its late fee be paid with another member's badge
+    executor.set_current_epoch(5);
+    let pay_receipt = pay_fee(&mut executor, &env, 4, env.other_account, env.other_key);
+    assert!(!pay_receipt.success);
+
+    // returning an overdue book charges the late fee to the member, who cannot borrow until it is paid
+    let return_receipt = return_book(&mut executor, &env, env.account, env.key);
+    assert!(return_receipt.success);
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(!borrow_receipt.success);
+    let pay_receipt = call_method(&mut executor, env.lib, "pay_outstanding_fees", vec![format!("4,{}", RADIX_TOKEN), format!("1,{}", env.member_badge_def)], env.account, env.key);
+    assert!(pay_receipt.success);
+    let borrow_receipt = borrow_dune(&mut executor, &env, env.account, env.key);
+    assert!(borrow_receipt.success);
+}
+
+fn new_library<L: Ledger>(executor: &mut TransactionExecutor<L>, account: Address, key: Address) -> Receipt {
+    let args = vec!["10".to_string(), "2".to_string(), RADIX_TOKEN.to_string(), "1".to_string(), "5".to_string(), "3".to_string(), "5".to_string(), "2".to_string(), "5".to_string()];
+    let package = executor.publish_package(include_code!("library"));
+    executor
+        .run(
+            TransactionBuilder::new(executor)
                 .call_function(package, "Library", "new", args, None)
                 .deposit_all_buckets(account)
                 .build(vec![key])
                 .unwrap(),
             false,
         )
-        .unwrap();
-    let lib = receipt.component(0).unwrap();
+        .unwrap()
+}
 
-    let xrd = format!("1,{}", RADIX_TOKEN);
-    let register_transaction = TransactionBuilder::new(&executor)
-        .call_method(lib, "register", vec![xrd], Some(account))
-        .deposit_all_buckets(account)
-        .build(vec![key])
-        .unwrap();
-    let register_receipt = executor.run(register_transaction, false).unwrap();
-    assert!(register_receipt.success);
+// creates the librarian and another account, and a library owned by the librarian
+fn setup<L: Ledger>(executor: &mut TransactionExecutor<L>) -> TestEnv {
+    let key = executor.new_public_key();
+    let account = executor.new_account(key);
+    let other_key = executor.new_public_key();
+    let other_account = executor.new_account(other_key);
 
-    // check print method for 1 less membership badge
-    let print_library_receipt = executor
+    let receipt = new_library(executor, account, key);
+    assert!(receipt.success);
+    TestEnv {
+        key,
+        account,
+        other_key,
+        other_account,
+        lib: receipt.component(0).unwrap(),
+        librarian_badge_def: receipt.resource_def(0).unwrap(),
+        loan_def: receipt.resource_def(3).unwrap(),
+        reservation_def: receipt.resource_def(4).unwrap(),
+        member_badge_def: receipt.resource_def(5).unwrap(),
+    }
+}
+
+fn add_dune<L: Ledger>(executor: &mut TransactionExecutor<L>, env: &TestEnv, copies: u32) -> Receipt {
+    call_method(executor, env.lib, "add_book", vec![DUNE.to_string(), "Dune".to_string(), "Frank Herbert".to_string(), copies.to_string(), format!("1,{}", env.librarian_badge_def)], env.account, env.key)
+}
+
+fn add_dune_and_register_both<L: Ledger>(executor: &mut TransactionExecutor<L>, env: &TestEnv, copies: u32) {
+    let add_receipt = add_dune(executor, env, copies);
+    assert!(add_receipt.success);
+    for (account, key) in [(env.account, env.key), (env.other_account, env.other_key)] {
+        let register_receipt = register(executor, env, account, key);
+        assert!(register_receipt.success);
+    }
+}
+
+// pays the membership price and deposit
+fn register<L: Ledger>(executor: &mut TransactionExecutor<L>, env: &TestEnv, account: Address, key: Address) -> Receipt {
+    call_method(executor, env.lib, "register", vec![format!("6,{}", RADIX_TOKEN)], account, key)
+}
+
+fn borrow_dune<L: Ledger>(executor: &mut TransactionExecutor<L>, env: &TestEnv, account: Address, key: Address) -> Receipt {
+    call_method(executor, env.lib, "borrow_book", vec![DUNE.to_string(), format!("1,{}", env.member_badge_def)], account, key)
+}
+
+fn borrow_reserved_book<L: Ledger>(executor: &mut TransactionExecutor<L>, env: &TestEnv) -> Receipt {
+    call_method(executor, env.lib, "borrow_reserved_book", vec![format!("1,{}", env.reservation_def), format!("1,{}", env.member_badge_def)], env.other_account, env.other_key)
+}
+
+fn return_book<L: Ledger>(executor: &mut TransactionExecutor<L>, env: &TestEnv, account: Address, key: Address) -> Receipt {
+    call_method(executor, env.lib, "return_book", vec![format!("1,{}", env.loan_def), format!("1,{}", env.member_badge_def)], account, key)
+}
+
+fn pay_fee<L: Ledger>(executor: &mut TransactionExecutor<L>, env: &TestEnv, fee: u32, account: Address, key: Address) -> Receipt {
+    call_method(executor, env.lib, "pay_fee", vec![format!("1,{}", env.loan_def), format!("{},{}", fee, RADIX_TOKEN), format!("1,{}", env.member_badge_def)], account, key)
+}
+
+fn call_method<L: Ledger>(executor: &mut TransactionExecutor<L>, component: Address, method: &str, args: Vec<String>, account: Address, key: Address) -> Receipt {
+    executor
         .run(
-            TransactionBuilder::new(&executor)
-                .call_method(lib, "print_library", vec![], None)
+            TransactionBuilder::new(executor)
+                .call_method(component, method, args, Some(account))
+                .drop_all_bucket_refs()
+                .deposit_all_buckets(account)
                 .build(vec![key])
                 .unwrap(),
             false,
         )
-        .unwrap();
-    assert!(print_library_receipt.success);
-    assert_eq!(
-        print_library_receipt.logs[1].1,
-        "Membership price: 1, memberships available: 9"
-    );
+        .unwrap()
 }
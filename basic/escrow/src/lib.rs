@@ -1,3 +1,5 @@
+mod multi_party_escrow;
+
 use scrypto::prelude::*;
 
 blueprint! {
@@ -8,11 +10,34 @@ blueprint! {
         account_b_badge: ResourceDef,
         account_a_accepted: bool,
         account_b_accepted: bool,
-        trade_canceled: bool
+        trade_canceled: bool,
+        // The exact amounts each party must deposit
+        amount_a: Decimal,
+        amount_b: Decimal,
+        // The last epoch the trade can be accepted in, afterwards the parties can withdraw their own tokens
+        deadline_epoch: u64,
+        // Rules on disputes raised by the parties
+        arbiter_badge: ResourceDef,
+        // The number of epochs after the deadline the arbiter has to rule, afterwards the parties can withdraw their own tokens
+        ruling_window: u64,
+        // The percentage of both vaults the arbiter earns for a ruling
+        arbiter_fee: Decimal,
+        fees_a: Vault,
+        fees_b: Vault,
+        trade_disputed: bool,
+        dispute_resolved: bool,
+        // The parts of the deposits that go to the other party after a ruling
+        settled_a: Vault,
+        settled_b: Vault
     }
 
     impl Escrow {
-        pub fn new(token_a_address: Address, token_b_address: Address) -> (Component, Bucket, Bucket) {
+        // The arbiter is chosen by the parties and proves their role with a badge they already hold
+        pub fn new(token_a_address: Address, token_b_address: Address, amount_a: Decimal, amount_b: Decimal, deadline_epoch: u64, arbiter_badge_address: Address, arbiter_fee: Decimal, ruling_window: u64) -> (Component, Bucket, Bucket) {
+            assert!(amount_a > Decimal::zero() && amount_b > Decimal::zero(), "The amounts to trade must be positive");
+            assert!(deadline_epoch >= Context::current_epoch(), "The deadline must not be in the past");
+            assert!(arbiter_fee >= Decimal::zero() && arbiter_fee <= 100.into(), "The arbiter fee must be a percentage");
+
             // Create the badges that will allow the component to authenticate the users
             let account_a_badge = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
                 .metadata("symbol", "BADGE A")
@@ -32,7 +57,19 @@ blueprint! {
                 account_b_badge: account_b_badge_resource,
                 account_a_accepted: false,
                 account_b_accepted: false,
-                trade_canceled: false
+                trade_canceled: false,
+                amount_a,
+                amount_b,
+                deadline_epoch,
+                arbiter_badge: arbiter_badge_address.into(),
+                ruling_window,
+                arbiter_fee,
+                fees_a: Vault::new(token_a_address),
+                fees_b: Vault::new(token_b_address),
+                trade_disputed: false,
+                dispute_resolved: false,
+                settled_a: Vault::new(token_a_address),
+                settled_b: Vault::new(token_b_address)
             }
             .instantiate();
 
@@ -42,26 +79,32 @@ blueprint! {
         // Allow the users to put the tokens they want to trade inside the component's vault
         #[auth(account_a_badge, account_b_badge)]
         pub fn put_tokens(&mut self, tokens: Bucket) {
-            let user_id = Self::get_user_id(&auth);
+            let user_id = self.get_user_id(&auth);
 
             assert!(!(self.account_a_accepted || self.account_b_accepted) , "Can't add more tokens when someone accepted");
             assert!(!self.trade_canceled, "The trade was canceled");
+            assert!(!self.is_expired(), "The deadline has passed");
 
             if user_id == self.account_a_badge.address() {
+                assert!(self.token_a.amount() + tokens.amount() <= self.amount_a, "You can't deposit more than the terms require");
                 self.token_a.put(tokens);
             } else {
+                assert!(self.token_b.amount() + tokens.amount() <= self.amount_b, "You can't deposit more than the terms require");
                 self.token_b.put(tokens);
             }
         }
 
-        // Allow the users to withdraw their tokens after both parties accepted or canceled
+        // Allow the users to withdraw their tokens after both parties accepted or canceled, or the deadline passed
+        // A disputed trade is refunded if the arbiter didn't rule within the ruling window
         #[auth(account_a_badge, account_b_badge)]
         pub fn withdraw(&mut self) -> Bucket {
-            let user_id = Self::get_user_id(&auth);
-            assert!(self.trade_canceled || (self.account_a_accepted && self.account_b_accepted), "The trade must be accepted or canceled");
+            let user_id = self.get_user_id(&auth);
+            let trade_accepted = self.account_a_accepted && self.account_b_accepted;
+            assert!(!self.trade_disputed || (!self.dispute_resolved && self.is_ruling_overdue()), "The trade is disputed");
+            assert!(self.trade_canceled || trade_accepted || self.is_expired(), "The trade must be accepted, canceled or past its deadline");
 
             if user_id == self.account_a_badge.address() {
-                if self.trade_canceled {
+                if !trade_accepted {
                     // Return added tokens
                     self.token_a.take_all()
                 } else {
@@ -69,7 +112,7 @@ blueprint! {
                     self.token_b.take_all()
                 }
             } else {
-                if self.trade_canceled {
+                if !trade_accepted {
                     // Return added tokens
                     self.token_b.take_all()
                 } else {
@@ -82,10 +125,12 @@ blueprint! {
         // Allow the users to accept the trade, after both parties added their tokens to the vault
         #[auth(account_a_badge, account_b_badge)]
         pub fn accept(&mut self) {
-            let user_id = Self::get_user_id(&auth);
+            let user_id = self.get_user_id(&auth);
 
-            assert!(self.token_a.amount() > 0.into() && self.token_b.amount() > 0.into(), "Both parties must add their tokens before you can accept");
+            assert!(!self.trade_disputed, "The trade is disputed");
+            assert!(self.token_a.amount() == self.amount_a && self.token_b.amount() == self.amount_b, "Both parties must add the agreed amounts before you can accept");
             assert!(!self.trade_canceled, "The trade was canceled");
+            assert!(!self.is_expired(), "The deadline has passed");
 
             if user_id == self.account_a_badge.address() {
                 assert!(!self.account_a_accepted, "You already accepted the offer !");
@@ -99,16 +144,83 @@ blueprint! {
         // Cancel the trade, must be done before both parties accepted
         #[auth(account_a_badge, account_b_badge)]
         pub fn cancel(&mut self) {
+            self.get_user_id(&auth);
+            assert!(!self.trade_disputed, "The trade is disputed");
             assert!(!(self.account_a_accepted && self.account_b_accepted), "The trade is already over, everyone accepted");
             assert!(!self.trade_canceled, "The trade is already canceled");
 
             self.trade_canceled = true;
         }
 
-        // Get user id from the provided badge
-        fn get_user_id(badge: &BucketRef) -> Address {
+        // Raise a dispute, the trade is frozen until the arbiter rules on it
+        #[auth(account_a_badge, account_b_badge)]
+        pub fn raise_dispute(&mut self) {
+            self.get_user_id(&auth);
+            assert!(!self.trade_disputed, "The trade is already disputed");
+            assert!(!(self.account_a_accepted && self.account_b_accepted), "The trade is already over, everyone accepted");
+            assert!(!self.trade_canceled, "The trade was canceled");
+            assert!(!self.is_expired(), "The deadline has passed");
+
+            self.trade_disputed = true;
+        }
+
+        // Allow the arbiter to rule on a dispute, `settle_percent` of both deposits go to the other party and the rest is refunded
+        // 100 settles the trade, 0 refunds both parties. The arbiter fee is taken from both vaults first
+        #[auth(arbiter_badge)]
+        pub fn resolve_dispute(&mut self, settle_percent: Decimal) {
+            self.get_user_id(&auth);
+            assert!(self.trade_disputed, "The trade is not disputed");
+            assert!(!self.dispute_resolved, "The dispute is already resolved");
+            assert!(!self.is_ruling_overdue(), "The ruling window has passed");
+            assert!(settle_percent >= Decimal::zero() && settle_percent <= 100.into(), "The settled part must be a percentage");
+
+            self.fees_a.put(self.token_a.take(self.token_a.amount() * self.arbiter_fee / 100));
+            self.fees_b.put(self.token_b.take(self.token_b.amount() * self.arbiter_fee / 100));
+            self.settled_a.put(self.token_a.take(self.token_a.amount() * settle_percent / 100));
+            self.settled_b.put(self.token_b.take(self.token_b.amount() * settle_percent / 100));
+            self.dispute_resolved = true;
+        }
+
+        // Allow the users to withdraw their share after the arbiter ruled on the dispute, returns the token A and token B parts
+        #[auth(account_a_badge, account_b_badge)]
+        pub fn withdraw_ruling(&mut self) -> (Bucket, Bucket) {
+            let user_id = self.get_user_id(&auth);
+            assert!(self.dispute_resolved, "The dispute must be resolved");
+
+            if user_id == self.account_a_badge.address() {
+                // The refunded part of the token A and the settled part of the token B
+                (self.token_a.take_all(), self.settled_b.take_all())
+            } else {
+                // The settled part of the token A and the refunded part of the token B
+                (self.settled_a.take_all(), self.token_b.take_all())
+            }
+        }
+
+        // Allow the arbiter to withdraw the fees earned with the ruling
+        #[auth(arbiter_badge)]
+        pub fn withdraw_fees(&mut self) -> (Bucket, Bucket) {
+            self.get_user_id(&auth);
+            (self.fees_a.take_all(), self.fees_b.take_all())
+        }
+
+        // Returns if the deadline has passed
+        fn is_expired(&self) -> bool {
+            Context::current_epoch() > self.deadline_epoch
+        }
+
+        // Returns if the arbiter can no longer rule on a dispute
+        fn is_ruling_overdue(&self) -> bool {
+            Context::current_epoch() > self.deadline_epoch + self.ruling_window
+        }
+
+        // Get user id from the provided badge, which must be one of the party badges or the arbiter badge
+        fn get_user_id(&self, badge: &BucketRef) -> Address {
             assert!(badge.amount() > 0.into(), "Invalid user proof");
             let user_id = badge.resource_address();
+            assert!(
+                user_id == self.account_a_badge.address() || user_id == self.account_b_badge.address() || user_id == self.arbiter_badge.address(),
+                "Invalid user proof"
+            );
             user_id
         }
     }
@@ -0,0 +1,210 @@
+use sbor::*;
+use scrypto::prelude::*;
+
+// Given to every party of the escrow, records the terms they signed up for
+#[derive(NftData)]
+struct PartyBadge {
+    deposits: String,
+    payouts: String,
+}
+
+#[derive(TypeId, Encode, Decode)]
+struct Party {
+    // The amounts of every resource the party must deposit and will receive
+    deposits: HashMap<Address, Decimal>,
+    payouts: HashMap<Address, Decimal>,
+    // The amounts of every resource the party has deposited so far
+    deposited: HashMap<Address, Decimal>,
+    accepted: bool,
+    withdrawn: bool,
+}
+
+blueprint! {
+    struct MultiPartyEscrow {
+        organizer_badge: ResourceDef,
+        // Allows the component to mint the party badges
+        minter: Vault,
+        party_badge: ResourceDef,
+        // Indexed by the ID of the party badges
+        parties: Vec<Party>,
+        // Holds the deposits of all parties, per resource
+        vaults: HashMap<Address, Vault>,
+        terms_locked: bool,
+        trade_settled: bool,
+        trade_canceled: bool
+    }
+
+    impl MultiPartyEscrow {
+        // Creates an escrow without parties, returns the organizer badge used to define the terms of the trade
+        pub fn new() -> (Component, Bucket) {
+            let organizer_badge = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+                .metadata("symbol", "ORGANIZER")
+                .initial_supply_fungible(1);
+
+            let minter = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+                .metadata("name", "Escrow Minter")
+                .initial_supply_fungible(1);
+
+            let party_badge = ResourceBuilder::new_non_fungible()
+                .metadata("symbol", "PARTY")
+                .flags(MINTABLE)
+                .badge(minter.resource_def(), MAY_MINT)
+                .no_initial_supply();
+
+            let component = Self {
+                organizer_badge: organizer_badge.resource_def(),
+                minter: Vault::with_bucket(minter),
+                party_badge,
+                parties: Vec::new(),
+                vaults: HashMap::new(),
+                terms_locked: false,
+                trade_settled: false,
+                trade_canceled: false
+            }
+            .instantiate();
+
+            (component, organizer_badge)
+        }
+
+        // Adds a party to the trade and returns their badge
+        // Both terms are lists of amounts and resources, like "500,<address>;10,<address>"
+        #[auth(organizer_badge)]
+        pub fn add_party(&mut self, deposits: String, payouts: String) -> Bucket {
+            assert!(!self.terms_locked, "The terms are locked");
+
+            let party = Party {
+                deposits: Self::parse_amounts(&deposits),
+                payouts: Self::parse_amounts(&payouts),
+                deposited: HashMap::new(),
+                accepted: false,
+                withdrawn: false
+            };
+            assert!(!party.deposits.is_empty() || !party.payouts.is_empty(), "A party must deposit or receive something");
+            for resource in party.deposits.keys() {
+                if !self.vaults.contains_key(resource) {
+                    self.vaults.insert(*resource, Vault::new(*resource));
+                }
+            }
+
+            let party_id = self.parties.len() as u128;
+            self.parties.push(party);
+            self.minter.authorize(|auth| self.party_badge.mint_nft(party_id, PartyBadge { deposits, payouts }, auth))
+        }
+
+        // Locks the terms, the parties can deposit from now on
+        // The payouts of every resource must add up to its deposits
+        #[auth(organizer_badge)]
+        pub fn lock_terms(&mut self) {
+            assert!(!self.terms_locked, "The terms are already locked");
+            assert!(self.parties.len() >= 2, "The trade needs at least two parties");
+
+            let mut balances: HashMap<Address, Decimal> = HashMap::new();
+            for party in &self.parties {
+                for (resource, amount) in &party.deposits {
+                    *balances.entry(*resource).or_insert(Decimal::zero()) += *amount;
+                }
+                for (resource, amount) in &party.payouts {
+                    *balances.entry(*resource).or_insert(Decimal::zero()) -= *amount;
+                }
+            }
+            for (resource, balance) in balances {
+                assert!(balance == Decimal::zero(), "The deposits and payouts of {} don't match", resource);
+            }
+
+            self.terms_locked = true;
+        }
+
+        // Allow the parties to put the tokens they must deposit inside the component's vaults
+        #[auth(party_badge)]
+        pub fn put_tokens(&mut self, tokens: Bucket) {
+            let party_id = Self::get_party_id(&auth);
+
+            assert!(self.terms_locked, "The terms are not locked yet");
+            assert!(!self.trade_canceled, "The trade was canceled");
+            assert!(!self.parties.iter().any(|party| party.accepted), "Can't add more tokens when someone accepted");
+
+            let resource = tokens.resource_address();
+            let party = &mut self.parties[party_id];
+            let required = *party.deposits.get(&resource).expect("You don't have to deposit this resource");
+            let deposited = party.deposited.entry(resource).or_insert(Decimal::zero());
+            assert!(*deposited + tokens.amount() <= required, "You can't deposit more than the terms require");
+
+            *deposited += tokens.amount();
+            self.vaults.get_mut(&resource).unwrap().put(tokens);
+        }
+
+        // Allow the parties to accept the trade, after everyone deposited the tokens required by the terms
+        // The trade settles as soon as every party accepted
+        #[auth(party_badge)]
+        pub fn accept(&mut self) {
+            let party_id = Self::get_party_id(&auth);
+
+            assert!(!self.trade_canceled, "The trade was canceled");
+            assert!(self.terms_locked && self.parties.iter().all(|party| Self::has_deposited(party)), "Every party must add their tokens before you can accept");
+            assert!(!self.parties[party_id].accepted, "You already accepted the offer !");
+
+            self.parties[party_id].accepted = true;
+            if self.parties.iter().all(|party| party.accepted) {
+                info!("Everyone accepted, the trade is settled");
+                self.trade_settled = true;
+            }
+        }
+
+        // Cancel the trade and refund everyone, must be done before every party accepted
+        #[auth(party_badge)]
+        pub fn cancel(&mut self) {
+            Self::get_party_id(&auth);
+            assert!(!self.trade_settled, "The trade is already over, everyone accepted");
+            assert!(!self.trade_canceled, "The trade is already canceled");
+
+            self.trade_canceled = true;
+        }
+
+        // Allow the parties to withdraw their payouts once the trade settled, or their deposits if it was canceled
+        #[auth(party_badge)]
+        pub fn withdraw(&mut self) -> Vec<Bucket> {
+            let party_id = Self::get_party_id(&auth);
+            assert!(self.trade_canceled || self.trade_settled, "The trade must be accepted or canceled");
+            assert!(!self.parties[party_id].withdrawn, "You already withdrew your tokens");
+
+            let party = &mut self.parties[party_id];
+            party.withdrawn = true;
+            let amounts = if self.trade_canceled {
+                // Return added tokens
+                &party.deposited
+            } else {
+                // Trade was accepted from every party, take the payouts
+                &party.payouts
+            };
+
+            amounts
+                .iter()
+                .map(|(resource, amount)| self.vaults.get_mut(resource).unwrap().take(*amount))
+                .collect()
+        }
+
+        // Get the party id from the provided badge
+        fn get_party_id(badge: &BucketRef) -> usize {
+            assert!(badge.amount() == 1.into(), "Invalid user proof");
+            badge.get_nft_id() as usize
+        }
+
+        // Returns if the party deposited everything the terms require
+        fn has_deposited(party: &Party) -> bool {
+            party.deposits.iter().all(|(resource, amount)| party.deposited.get(resource) == Some(amount))
+        }
+
+        // Parses a list of amounts and resources, like "500,<address>;10,<address>"
+        fn parse_amounts(terms: &str) -> HashMap<Address, Decimal> {
+            let mut amounts: HashMap<Address, Decimal> = HashMap::new();
+            for term in terms.split(';').filter(|term| !term.is_empty()) {
+                let (amount, resource) = term.split_once(',').expect("Terms must be written as <amount>,<resource>");
+                let amount = Decimal::from_str(amount).expect("Invalid amount");
+                let resource = Address::from_str(resource).expect("Invalid resource address");
+                assert!(amount > Decimal::zero(), "Amounts must be positive");
+                *amounts.entry(resource).or_insert(Decimal::zero()) += amount;
+            }
+            amounts
+        }
+    }
+}
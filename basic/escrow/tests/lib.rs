@@ -86,6 +86,50 @@ impl<'a, L: Ledger> TestUtils<'a, L> {
                 .unwrap();
     }
 
+    // Sends a single NFT, identified by its ID, to another account
+    pub fn send_nft(&mut self, account: Address, id: u128, token_address: String) {
+        let user = self.get_user_or_fail();
+        let receipt = self.executor
+                .run(
+                    TransactionBuilder::new(&self.executor)
+                        .call_method(account, "deposit", vec![format!("#{},{}", id, token_address)], Some(user.address))
+                        .build(vec![user.pub_key])
+                        .unwrap(),
+                    false,
+                )
+                .unwrap();
+        assert!(receipt.success);
+    }
+
+    pub fn get_balance(&self, account: Address, token: Address) -> Decimal {
+        let ledger = self.executor.ledger();
+        let account_component = ledger.get_component(account).unwrap();
+        let mut vaults = vec![];
+        let _res = radix_engine::utils::format_data_with_ledger(
+            account_component
+                .state(radix_engine::model::Actor::SuperUser)
+                .unwrap(),
+            ledger,
+            &mut vaults,
+        ).unwrap();
+
+        for vid in vaults {
+            let vault = ledger.get_vault(vid).unwrap();
+            let resource_def_address = vault
+                .resource_address(radix_engine::model::Actor::SuperUser)
+                .unwrap();
+            if token == resource_def_address {
+                return vault.amount(radix_engine::model::Actor::SuperUser).unwrap();
+            }
+        }
+
+        Decimal::zero()
+    }
+
+    pub fn set_current_epoch(&mut self, epoch: u64) {
+        self.executor.set_current_epoch(epoch);
+    }
+
     pub fn create_token(&mut self, max_supply: Decimal) -> ResourceDef {
         let user = self.get_user_or_fail();
         let receipt = self.executor
@@ -146,12 +190,13 @@ fn test_simple_trade() {
 
     let token1 = utils.act_as(account1).create_token(8_000.into());
     let token2 = utils.act_as(account2).create_token(8_000_000.into());
+    let arbiter_badge = utils.act_as(account1).create_token(1.into());
 
     let receipt = utils.act_as(account1)
             .call_function(
                 "Escrow", 
                 "new", 
-                vec![token1.address().to_string(), token2.address().to_string(), account1.address.to_string(), account2.address.to_string()]
+                vec![token1.address().to_string(), token2.address().to_string(), "1000".to_string(), "1000".to_string(), "10".to_string(), arbiter_badge.address().to_string(), "10".to_string(), "5".to_string()]
             );
     assert!(receipt.success);
     let (resources, components, _) = utils.get_call_results(receipt);
@@ -187,6 +232,11 @@ fn test_simple_trade() {
             .call_method(component, "put_tokens", vec![format!("500,{}", token2.address()), format!("1,{}", badge2)]);
     assert!(receipt.success);
 
+    // The deposits match the terms, so no more tokens can be added
+    let receipt = utils.act_as(account2)
+            .call_method(component, "put_tokens", vec![format!("1,{}", token2.address()), format!("1,{}", badge2)]);
+    assert!(!receipt.success);
+
     // First user accepts the trade
     let receipt = utils.act_as(account1)
             .call_method(component, "accept", vec![format!("1,{}", badge1)]);
@@ -197,3 +247,281 @@ fn test_simple_trade() {
             .call_method(component, "accept", vec![format!("1,{}", badge2)]);
     assert!(receipt.success);
 }
+
+#[test]
+fn test_deadline() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut utils = TestUtils::new(&mut ledger);
+    let account1 = utils.create_account();
+    let account2 = utils.create_account();
+
+    let token1 = utils.act_as(account1).create_token(8_000.into());
+    let token2 = utils.act_as(account2).create_token(8_000_000.into());
+    let arbiter_badge = utils.act_as(account1).create_token(1.into());
+
+    let receipt = utils.act_as(account1)
+            .call_function(
+                "Escrow",
+                "new",
+                vec![token1.address().to_string(), token2.address().to_string(), "1000".to_string(), "1000".to_string(), "10".to_string(), arbiter_badge.address().to_string(), "10".to_string(), "5".to_string()]
+            );
+    assert!(receipt.success);
+    let (resources, components, _) = utils.get_call_results(receipt);
+
+    let badge1 = resources.get(0).unwrap();
+    let badge2 = resources.get(1).unwrap();
+    let component = components.get(0).unwrap();
+    utils.act_as(account1).send_tokens(account2.address, 1, badge2.to_string());
+
+    // Only part of the agreed amount is deposited, so the trade can't be accepted
+    let receipt = utils.act_as(account1)
+            .call_method(component, "put_tokens", vec![format!("1000,{}", token1.address()), format!("1,{}", badge1)]);
+    assert!(receipt.success);
+    let receipt = utils.act_as(account2)
+            .call_method(component, "put_tokens", vec![format!("1,{}", token2.address()), format!("1,{}", badge2)]);
+    assert!(receipt.success);
+    let receipt = utils.act_as(account1)
+            .call_method(component, "accept", vec![format!("1,{}", badge1)]);
+    assert!(!receipt.success);
+
+    // The tokens can't be withdrawn before the deadline
+    let receipt = utils.act_as(account1)
+            .call_method(component, "withdraw", vec![format!("1,{}", badge1)]);
+    assert!(!receipt.success);
+
+    // After the deadline, both parties get their own tokens back without canceling
+    utils.set_current_epoch(11);
+    let receipt = utils.act_as(account2)
+            .call_method(component, "put_tokens", vec![format!("999,{}", token2.address()), format!("1,{}", badge2)]);
+    assert!(!receipt.success);
+    let receipt = utils.act_as(account1)
+            .call_method(component, "withdraw", vec![format!("1,{}", badge1)]);
+    assert!(receipt.success);
+    let receipt = utils.act_as(account2)
+            .call_method(component, "withdraw", vec![format!("1,{}", badge2)]);
+    assert!(receipt.success);
+}
+
+#[test]
+fn test_dispute() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut utils = TestUtils::new(&mut ledger);
+    let account1 = utils.create_account();
+    let account2 = utils.create_account();
+    let arbiter = utils.create_account();
+
+    let token1 = utils.act_as(account1).create_token(8_000.into());
+    let token2 = utils.act_as(account2).create_token(8_000_000.into());
+    // The arbiter brings their own badge
+    let arbiter_badge = utils.act_as(arbiter).create_token(1.into());
+
+    let receipt = utils.act_as(account1)
+            .call_function(
+                "Escrow",
+                "new",
+                vec![token1.address().to_string(), token2.address().to_string(), "1000".to_string(), "1000".to_string(), "10".to_string(), arbiter_badge.address().to_string(), "10".to_string(), "5".to_string()]
+            );
+    assert!(receipt.success);
+    let (resources, components, _) = utils.get_call_results(receipt);
+
+    let badge1 = resources.get(0).unwrap();
+    let badge2 = resources.get(1).unwrap();
+    let component = components.get(0).unwrap();
+    utils.act_as(account1).send_tokens(account2.address, 1, badge2.to_string());
+
+    let receipt = utils.act_as(account1)
+            .call_method(component, "put_tokens", vec![format!("1000,{}", token1.address()), format!("1,{}", badge1)]);
+    assert!(receipt.success);
+    let receipt = utils.act_as(account2)
+            .call_method(component, "put_tokens", vec![format!("1000,{}", token2.address()), format!("1,{}", badge2)]);
+    assert!(receipt.success);
+
+    // The arbiter can't rule before a dispute is raised
+    let receipt = utils.act_as(arbiter)
+            .call_method(component, "resolve_dispute", vec!["50".to_string(), format!("1,{}", arbiter_badge.address())]);
+    assert!(!receipt.success);
+
+    let receipt = utils.act_as(account2)
+            .call_method(component, "raise_dispute", vec![format!("1,{}", badge2)]);
+    assert!(receipt.success);
+
+    // The trade is frozen
+    for method in ["accept", "cancel", "withdraw"] {
+        let receipt = utils.act_as(account1).call_method(component, method, vec![format!("1,{}", badge1)]);
+        assert!(!receipt.success);
+    }
+
+    // Only the arbiter can rule on the dispute
+    let receipt = utils.act_as(account1)
+            .call_method(component, "resolve_dispute", vec!["100".to_string(), format!("1,{}", badge1)]);
+    assert!(!receipt.success);
+    let receipt = utils.act_as(arbiter)
+            .call_method(component, "resolve_dispute", vec!["50".to_string(), format!("1,{}", arbiter_badge.address())]);
+    assert!(receipt.success);
+
+    for (account, badge) in [(account1, badge1), (account2, badge2)] {
+        let receipt = utils.act_as(account).call_method(component, "withdraw_ruling", vec![format!("1,{}", badge)]);
+        assert!(receipt.success);
+    }
+    let receipt = utils.act_as(arbiter)
+            .call_method(component, "withdraw_fees", vec![format!("1,{}", arbiter_badge.address())]);
+    assert!(receipt.success);
+
+    // The arbiter earns 10% of both deposits, then half of what is left goes to the other party
+    assert_eq!(utils.get_balance(arbiter.address, token1.address()), 100.into());
+    assert_eq!(utils.get_balance(arbiter.address, token2.address()), 100.into());
+    assert_eq!(utils.get_balance(account1.address, token1.address()), 7_450.into());
+    assert_eq!(utils.get_balance(account1.address, token2.address()), 450.into());
+    assert_eq!(utils.get_balance(account2.address, token1.address()), 450.into());
+    assert_eq!(utils.get_balance(account2.address, token2.address()), 7_999_450.into());
+}
+
+#[test]
+fn test_unresolved_dispute() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut utils = TestUtils::new(&mut ledger);
+    let account1 = utils.create_account();
+    let account2 = utils.create_account();
+    let arbiter = utils.create_account();
+
+    let token1 = utils.act_as(account1).create_token(8_000.into());
+    let token2 = utils.act_as(account2).create_token(8_000_000.into());
+    let arbiter_badge = utils.act_as(arbiter).create_token(1.into());
+
+    let receipt = utils.act_as(account1)
+            .call_function(
+                "Escrow",
+                "new",
+                vec![token1.address().to_string(), token2.address().to_string(), "1000".to_string(), "1000".to_string(), "10".to_string(), arbiter_badge.address().to_string(), "10".to_string(), "5".to_string()]
+            );
+    assert!(receipt.success);
+    let (resources, components, _) = utils.get_call_results(receipt);
+
+    let badge1 = resources.get(0).unwrap();
+    let badge2 = resources.get(1).unwrap();
+    let component = components.get(0).unwrap();
+    utils.act_as(account1).send_tokens(account2.address, 1, badge2.to_string());
+
+    let receipt = utils.act_as(account1)
+            .call_method(component, "put_tokens", vec![format!("1000,{}", token1.address()), format!("1,{}", badge1)]);
+    assert!(receipt.success);
+    let receipt = utils.act_as(account2)
+            .call_method(component, "put_tokens", vec![format!("1000,{}", token2.address()), format!("1,{}", badge2)]);
+    assert!(receipt.success);
+    let receipt = utils.act_as(account1)
+            .call_method(component, "raise_dispute", vec![format!("1,{}", badge1)]);
+    assert!(receipt.success);
+
+    // The trade stays frozen until the ruling window, 5 epochs after the deadline, has passed
+    utils.set_current_epoch(15);
+    let receipt = utils.act_as(account1)
+            .call_method(component, "withdraw", vec![format!("1,{}", badge1)]);
+    assert!(!receipt.success);
+
+    // The arbiter never ruled, so both parties get their own tokens back and the arbiter can't rule anymore
+    utils.set_current_epoch(16);
+    let receipt = utils.act_as(arbiter)
+            .call_method(component, "resolve_dispute", vec!["100".to_string(), format!("1,{}", arbiter_badge.address())]);
+    assert!(!receipt.success);
+    for (account, badge) in [(account1, badge1), (account2, badge2)] {
+        let receipt = utils.act_as(account).call_method(component, "withdraw", vec![format!("1,{}", badge)]);
+        assert!(receipt.success);
+    }
+    assert_eq!(utils.get_balance(account1.address, token1.address()), 8_000.into());
+    assert_eq!(utils.get_balance(account2.address, token2.address()), 8_000_000.into());
+}
+
+#[test]
+fn test_multi_party_trade() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut utils = TestUtils::new(&mut ledger);
+    let account1 = utils.create_account();
+    let account2 = utils.create_account();
+    let account3 = utils.create_account();
+
+    let token1 = utils.act_as(account1).create_token(8_000.into());
+    let token2 = utils.act_as(account2).create_token(8_000.into());
+    let token3 = utils.act_as(account3).create_token(8_000.into());
+
+    let receipt = utils.act_as(account1).call_function("MultiPartyEscrow", "new", vec![]);
+    assert!(receipt.success);
+    let (resources, components, _) = utils.get_call_results(receipt);
+    let organizer_badge = resources.get(0).unwrap();
+    let party_badge = resources.get(2).unwrap();
+    let component = components.get(0).unwrap();
+
+    // Every party gives their token to the next one
+    let trades = [(token1, token2), (token2, token3), (token3, token1)];
+    for (deposit, payout) in trades {
+        let receipt = utils.act_as(account1)
+                .call_method(component, "add_party", vec![format!("100,{}", deposit.address()), format!("100,{}", payout.address()), format!("1,{}", organizer_badge)]);
+        assert!(receipt.success);
+    }
+
+    // The terms must be locked before depositing
+    let receipt = utils.act_as(account1)
+            .call_method(component, "put_tokens", vec![format!("100,{}", token1.address()), format!("1,{}", party_badge)]);
+    assert!(!receipt.success);
+    let receipt = utils.act_as(account1)
+            .call_method(component, "lock_terms", vec![format!("1,{}", organizer_badge)]);
+    assert!(receipt.success);
+
+    // Send the badges to the other parties, account1 keeps the badge #0 of the first party
+    utils.act_as(account1).send_nft(account2.address, 1, party_badge.to_string());
+    utils.act_as(account1).send_nft(account3.address, 2, party_badge.to_string());
+
+    // Depositing more than the terms require or another resource fails
+    let receipt = utils.act_as(account1)
+            .call_method(component, "put_tokens", vec![format!("101,{}", token1.address()), format!("1,{}", party_badge)]);
+    assert!(!receipt.success);
+    let receipt = utils.act_as(account2)
+            .call_method(component, "put_tokens", vec![format!("100,{}", token2.address()), format!("1,{}", party_badge)]);
+    assert!(receipt.success);
+
+    // Nobody can accept before everyone deposited
+    let receipt = utils.act_as(account2)
+            .call_method(component, "accept", vec![format!("1,{}", party_badge)]);
+    assert!(!receipt.success);
+
+    let receipt = utils.act_as(account1)
+            .call_method(component, "put_tokens", vec![format!("100,{}", token1.address()), format!("1,{}", party_badge)]);
+    assert!(receipt.success);
+    let receipt = utils.act_as(account3)
+            .call_method(component, "put_tokens", vec![format!("100,{}", token3.address()), format!("1,{}", party_badge)]);
+    assert!(receipt.success);
+
+    for account in [account1, account2] {
+        let receipt = utils.act_as(account).call_method(component, "accept", vec![format!("1,{}", party_badge)]);
+        assert!(receipt.success);
+    }
+
+    // The payouts can only be withdrawn once everyone accepted
+    let receipt = utils.act_as(account1)
+            .call_method(component, "withdraw", vec![format!("1,{}", party_badge)]);
+    assert!(!receipt.success);
+    let receipt = utils.act_as(account3)
+            .call_method(component, "accept", vec![format!("1,{}", party_badge)]);
+    assert!(receipt.success);
+
+    // The trade is settled and can't be canceled anymore
+    let receipt = utils.act_as(account2)
+            .call_method(component, "cancel", vec![format!("1,{}", party_badge)]);
+    assert!(!receipt.success);
+    for account in [account1, account2, account3] {
+        let receipt = utils.act_as(account).call_method(component, "withdraw", vec![format!("1,{}", party_badge)]);
+        assert!(receipt.success);
+    }
+
+    // Every party received the payout of their own terms
+    let balances = [
+        (account1, [7_900, 0, 100]),
+        (account2, [100, 7_900, 0]),
+        (account3, [0, 100, 7_900])
+    ];
+    for (account, amounts) in balances {
+        for (token, amount) in [token1, token2, token3].iter().zip(amounts) {
+            assert_eq!(utils.get_balance(account.address, token.address()), amount.into());
+        }
+        assert_eq!(utils.get_balance(account.address, *party_badge), 1.into());
+    }
+}
@@ -1,72 +1,284 @@
+use sbor::*;
 use scrypto::prelude::*;
 
-// Component allowing multiple parties to approve a transaction
-// before it gets sent
+// Identifies a signer of the multisig
+#[derive(NftData)]
+struct Signer {
+    // The epoch the signer was added in
+    added_epoch: u64
+}
+
+// What a proposal does once enough signers approved it
+#[derive(TypeId, Encode, Decode, Clone)]
+enum Action {
+    // Send tokens held by the multisig
+    Transfer { resource: Address, amount: Decimal, destination: Address },
+    // Mint a new signer badge and send it to the destination account
+    AddSigner { destination: Address },
+    // Revoke a signer badge, it can't be used anymore
+    RemoveSigner { signer_id: u128 },
+    // Revoke a lost signer badge and send a new one to the destination account
+    ReplaceSigner { signer_id: u128, destination: Address },
+    ChangeThreshold { min_required_sig: usize }
+}
+
+#[derive(TypeId, Encode, Decode)]
+struct Proposal {
+    action: Action,
+    // The last epoch the proposal can be approved in
+    expiry_epoch: u64,
+    // The ids of the signer badges that approved the proposal
+    approvals: Vec<u128>,
+    executed: bool
+}
+
+// Component holding tokens that multiple parties must approve
+// before they get sent
 blueprint! {
     struct MultiSigMaker {
         badge_minter_badge: Vault,
         signer_badge: ResourceDef,
-        tokens: Vault,
+        // The tokens held by the multisig, per resource
+        vaults: HashMap<Address, Vault>,
         min_required_sig: usize,
-        badges_approved: usize,
-        destination: Account
+        // The ids of the signer badges that can still be used
+        signers: Vec<u128>,
+        next_signer_id: u128,
+        // Indexed by the proposal id
+        proposals: Vec<Proposal>
     }
 
     impl MultiSigMaker {
 
         /*
-         * nb_badges: The number of people that can approve the transaction
-         * min_required_sig: The minimum amount of people that needs to approve before the transaction gets sent
-         * destination: The receiver of the transaction
-         * amount: Bucket containing the tokens for the transaction
+         * nb_badges: The number of people that can approve the transactions
+         * min_required_sig: The minimum amount of people that needs to approve before a transaction gets sent
          */
-        pub fn new(nb_badges: usize, min_required_sig: usize, destination: Address, amount: Bucket) -> (Component, Bucket) {
-            assert!(min_required_sig <= nb_badges, "Min required sig can't be greater than amount of badges");
+        pub fn new(nb_badges: usize, min_required_sig: usize) -> (Component, Bucket) {
+            Self::check_threshold(min_required_sig, nb_badges);
 
             // This badge is used to create the initial supply of signer badges
-            // and burn them when people approve
+            // and the badges of the signers added later
             let badge_minter_badge = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
                 .initial_supply_fungible(1);
 
             // This badge is used to authenticate the users so that only
-            // people with this badge can approve the transaction
-            let badge_resourcedef = ResourceBuilder::new_fungible(DIVISIBILITY_NONE)
+            // people with this badge can propose and approve transactions.
+            // Every signer gets their own badge, so approvals can be tracked per signer
+            let badge_resourcedef = ResourceBuilder::new_non_fungible()
                 .metadata("name", "MultiSig Signer Badge")
-                .flags(MINTABLE | BURNABLE)
-                .badge(badge_minter_badge.resource_def(), MAY_MINT | MAY_BURN)
+                .flags(MINTABLE)
+                .badge(badge_minter_badge.resource_def(), MAY_MINT)
                 .no_initial_supply();
 
             // Mint the badges
-            let badges = badge_resourcedef.mint(nb_badges as u64, badge_minter_badge.present());
+            let badges = Bucket::new(badge_resourcedef.address());
+            for signer_id in 0..nb_badges {
+                let signer = Signer { added_epoch: Context::current_epoch() };
+                badges.put(badge_resourcedef.mint_nft(signer_id as u128, signer, badge_minter_badge.present()));
+            }
 
             let component = Self {
-                tokens: Vault::with_bucket(amount),
+                vaults: HashMap::new(),
                 min_required_sig: min_required_sig,
                 badge_minter_badge: Vault::with_bucket(badge_minter_badge),
                 signer_badge: badge_resourcedef,
-                badges_approved: 0,
-                destination: Account::from(destination)
+                signers: (0..nb_badges as u128).collect(),
+                next_signer_id: nb_badges as u128,
+                proposals: Vec::new()
             }
             .instantiate();
 
             (component, badges)
         }
 
-        pub fn approve(&mut self, auth_badge: Bucket) {
-            assert!(auth_badge.amount() > Decimal::zero(), "Invalid auth");
-            assert!(auth_badge.resource_def() == self.signer_badge, "Invalid badge");
-            assert!(self.badges_approved < self.min_required_sig, "Transaction already approved by majority");
-            
-            // Burn the badge to only allow one call to approve per badge
-            self.badge_minter_badge.authorize(|badge| {
-                self.signer_badge.burn_with_auth(auth_badge, badge);
+        // Anyone can fund the multisig, with any resource
+        pub fn deposit(&mut self, tokens: Bucket) {
+            let resource = tokens.resource_address();
+            if !self.vaults.contains_key(&resource) {
+                self.vaults.insert(resource, Vault::new(resource));
+            }
+            self.vaults.get_mut(&resource).unwrap().put(tokens);
+        }
+
+        /*
+         * Propose to send tokens held by the multisig, returns the proposal id
+         * resource: The resource to send
+         * amount: The amount to send
+         * destination: The receiver of the transaction
+         * expiry_epoch: The last epoch the proposal can be approved in
+         */
+        #[auth(signer_badge)]
+        pub fn propose(&mut self, resource: Address, amount: Decimal, destination: Address, expiry_epoch: u64) -> usize {
+            self.get_signer_id(&auth);
+            assert!(amount > Decimal::zero(), "Invalid amount");
+            self.add_proposal(Action::Transfer { resource, amount, destination }, expiry_epoch)
+        }
+
+        // Propose to add a signer, their badge is sent to the destination account
+        #[auth(signer_badge)]
+        pub fn propose_add_signer(&mut self, destination: Address, expiry_epoch: u64) -> usize {
+            self.get_signer_id(&auth);
+            self.add_proposal(Action::AddSigner { destination }, expiry_epoch)
+        }
+
+        // Propose to remove a signer, their badge can't be used anymore
+        #[auth(signer_badge)]
+        pub fn propose_remove_signer(&mut self, signer_id: u128, expiry_epoch: u64) -> usize {
+            self.get_signer_id(&auth);
+            assert!(self.signers.contains(&signer_id), "Signer doesn't exist");
+            self.add_proposal(Action::RemoveSigner { signer_id }, expiry_epoch)
+        }
+
+        // Propose to replace the badge of a signer, for example when they lost their key
+        #[auth(signer_badge)]
+        pub fn propose_replace_signer(&mut self, signer_id: u128, destination: Address, expiry_epoch: u64) -> usize {
+            self.get_signer_id(&auth);
+            assert!(self.signers.contains(&signer_id), "Signer doesn't exist");
+            self.add_proposal(Action::ReplaceSigner { signer_id, destination }, expiry_epoch)
+        }
+
+        // Propose to change the minimum amount of people that needs to approve a proposal
+        // The threshold is checked against the signers when the proposal is executed, so signers can be added by an earlier proposal
+        #[auth(signer_badge)]
+        pub fn propose_change_threshold(&mut self, min_required_sig: usize, expiry_epoch: u64) -> usize {
+            self.get_signer_id(&auth);
+            assert!(min_required_sig > 0, "At least one signature must be required");
+            self.add_proposal(Action::ChangeThreshold { min_required_sig }, expiry_epoch)
+        }
+
+        #[auth(signer_badge)]
+        pub fn approve(&mut self, proposal_id: usize) {
+            let signer_id = self.get_signer_id(&auth);
+            self.check_pending_proposal(proposal_id);
+            let proposal = &mut self.proposals[proposal_id];
+
+            // Only allow one approval per badge
+            assert!(!proposal.approvals.contains(&signer_id), "You already approved this proposal");
+            proposal.approvals.push(signer_id);
+
+            if proposal.approvals.len() >= self.min_required_sig {
+                proposal.executed = true;
+                let action = proposal.action.clone();
+                self.execute(action);
+                info!("Proposal {} executed", proposal_id);
+            }
+        }
+
+        // Withdraw an approval, only possible before the transaction is sent
+        #[auth(signer_badge)]
+        pub fn revoke(&mut self, proposal_id: usize) {
+            let signer_id = self.get_signer_id(&auth);
+            self.check_pending_proposal(proposal_id);
+            let proposal = &mut self.proposals[proposal_id];
+
+            assert!(proposal.approvals.contains(&signer_id), "You didn't approve this proposal");
+            proposal.approvals.retain(|id| *id != signer_id);
+        }
+
+        // Prints the tokens held by the multisig and the pending proposals
+        pub fn print_proposals(&self) {
+            for (resource, vault) in &self.vaults {
+                info!("Holding {} of {}", vault.amount(), resource);
+            }
+            info!("Signers: {:?}, required signatures: {}", self.signers, self.min_required_sig);
+            let current_epoch = Context::current_epoch();
+            for (proposal_id, proposal) in self.proposals.iter().enumerate() {
+                if !proposal.executed && current_epoch <= proposal.expiry_epoch {
+                    let description = match &proposal.action {
+                        Action::Transfer { resource, amount, destination } => format!("send {} of {} to {}", amount, resource, destination),
+                        Action::AddSigner { destination } => format!("add a signer for {}", destination),
+                        Action::RemoveSigner { signer_id } => format!("remove signer {}", signer_id),
+                        Action::ReplaceSigner { signer_id, destination } => format!("replace signer {} with a signer for {}", signer_id, destination),
+                        Action::ChangeThreshold { min_required_sig } => format!("require {} signatures", min_required_sig)
+                    };
+                    info!("Proposal {}: {}, approved by {}/{}, expires at epoch {}",
+                        proposal_id, description, proposal.approvals.len(), self.min_required_sig, proposal.expiry_epoch);
+                }
+            }
+        }
+
+        // Get the signer id from the provided badge, removed signers can't be used anymore
+        fn get_signer_id(&self, badge: &BucketRef) -> u128 {
+            assert!(badge.amount() == Decimal::one(), "Invalid auth");
+            let signer_id = badge.get_nft_id();
+            assert!(self.signers.contains(&signer_id), "This signer was removed");
+            signer_id
+        }
+
+        // Checks the threshold can be reached by the signers
+        fn check_threshold(min_required_sig: usize, nb_signers: usize) {
+            assert!(min_required_sig > 0, "At least one signature must be required");
+            assert!(min_required_sig <= nb_signers, "Min required sig can't be greater than amount of signers");
+        }
+
+        fn add_proposal(&mut self, action: Action, expiry_epoch: u64) -> usize {
+            assert!(expiry_epoch >= Context::current_epoch(), "The expiry epoch can't be in the past");
+
+            self.proposals.push(Proposal {
+                action,
+                expiry_epoch,
+                approvals: Vec::new(),
+                executed: false
             });
+            let proposal_id = self.proposals.len() - 1;
+            info!("Proposal {} created", proposal_id);
+            proposal_id
+        }
+
+        fn execute(&mut self, action: Action) {
+            match action {
+                Action::Transfer { resource, amount, destination } => {
+                    // Send the transaction
+                    let vault = self.vaults.get_mut(&resource).expect("The multisig doesn't hold this resource");
+                    assert!(vault.amount() >= amount, "Not enough tokens to send the transaction");
+                    Account::from(destination).deposit(vault.take(amount));
+                }
+                Action::AddSigner { destination } => {
+                    self.mint_signer_badge(destination);
+                }
+                Action::RemoveSigner { signer_id } => {
+                    self.remove_signer(signer_id);
+                    Self::check_threshold(self.min_required_sig, self.signers.len());
+                }
+                Action::ReplaceSigner { signer_id, destination } => {
+                    self.remove_signer(signer_id);
+                    self.mint_signer_badge(destination);
+                }
+                Action::ChangeThreshold { min_required_sig } => {
+                    Self::check_threshold(min_required_sig, self.signers.len());
+                    self.min_required_sig = min_required_sig;
+                }
+            }
+        }
+
+        fn mint_signer_badge(&mut self, destination: Address) {
+            let signer_id = self.next_signer_id;
+            let signer = Signer { added_epoch: Context::current_epoch() };
+            let badge = self.badge_minter_badge.authorize(|auth| self.signer_badge.mint_nft(signer_id, signer, auth));
+            Account::from(destination).deposit(badge);
+
+            self.signers.push(signer_id);
+            self.next_signer_id += 1;
+            info!("Signer {} added", signer_id);
+        }
 
-            self.badges_approved += 1;
-            if self.badges_approved >= self.min_required_sig {
-                // Send the transaction
-                self.destination.deposit(self.tokens.take_all());
+        // Revokes the badge of the signer, and their approvals of pending proposals
+        fn remove_signer(&mut self, signer_id: u128) {
+            assert!(self.signers.contains(&signer_id), "Signer doesn't exist");
+            self.signers.retain(|id| *id != signer_id);
+            for proposal in self.proposals.iter_mut().filter(|proposal| !proposal.executed) {
+                proposal.approvals.retain(|id| *id != signer_id);
             }
+            info!("Signer {} removed", signer_id);
+        }
+
+        // Checks the proposal exists and can still be approved
+        fn check_pending_proposal(&self, proposal_id: usize) {
+            assert!(proposal_id < self.proposals.len(), "Proposal doesn't exist");
+            let proposal = &self.proposals[proposal_id];
+            assert!(!proposal.executed, "Transaction already approved by majority");
+            assert!(Context::current_epoch() <= proposal.expiry_epoch, "Proposal expired");
         }
     }
 }
@@ -19,7 +19,7 @@ fn test_multisig() {
 
     // Test the `new` function.
     let transaction1 = TransactionBuilder::new(&executor)
-        .call_function(package, "MultiSigMaker", "new", vec!["2".to_string(), "2".to_string(), format!("{}", account3), format!("1000,{}", RADIX_TOKEN)], Some(account1))
+        .call_function(package, "MultiSigMaker", "new", vec!["2".to_string(), "2".to_string()], Some(account1))
         .deposit_all_buckets(account1)
         .build(vec![key1])
         .unwrap();
@@ -38,9 +38,28 @@ fn test_multisig() {
     executor.run(send_badge, false).unwrap();
 
     let component = receipt1.component(0).unwrap();
+
+    // Fund the multisig
+    let fund = TransactionBuilder::new(&executor)
+        .call_method(component, "deposit", vec![format!("1000,{}", RADIX_TOKEN)], Some(account1))
+        .build(vec![key1])
+        .unwrap();
+    assert!(executor.run(fund, false).unwrap().success);
+    assert_eq!(get_balance(&executor, component, RADIX_TOKEN), 1000.into());
+
+    // Propose two transactions to account 3, only the second one is approved
+    for amount in ["2000", "1000"] {
+        let propose = TransactionBuilder::new(&executor)
+            .call_method(component, "propose", vec![format!("{}", RADIX_TOKEN), amount.to_string(), format!("{}", account3), "10".to_string(), format!("1,{}", badge_resource_def)], Some(account1))
+            .drop_all_bucket_refs()
+            .build(vec![key1])
+            .unwrap();
+        assert!(executor.run(propose, false).unwrap().success);
+    }
+
     // Accept from account1
     let transaction2 = TransactionBuilder::new(&executor)
-        .call_method(component, "approve", vec![format!("1,{:?}", badge_resource_def)], Some(account1))
+        .call_method(component, "approve", vec!["1".to_string(), format!("1,{:?}", badge_resource_def)], Some(account1))
         .build(vec![key1])
         .unwrap();
     let receipt2 = executor.run(transaction2, false).unwrap();
@@ -49,10 +68,153 @@ fn test_multisig() {
 
     // Accept from account2
     let transaction3 = TransactionBuilder::new(&executor)
-        .call_method(component, "approve", vec![format!("1,{:?}", badge_resource_def)], Some(account2))
+        .call_method(component, "approve", vec!["1".to_string(), format!("1,{:?}", badge_resource_def)], Some(account2))
         .build(vec![key2])
         .unwrap();
     let receipt3 = executor.run(transaction3, false).unwrap();
     println!("{:?}\n", receipt3);
     assert!(receipt3.success);
+
+    // The second approval executed the transfer
+    assert_eq!(get_balance(&executor, account3, RADIX_TOKEN), 1_001_000.into());
+    assert_eq!(get_balance(&executor, component, RADIX_TOKEN), 0.into());
+
+    // The badges are not burned, so account 1 can approve the other proposal, but only once
+    let transaction4 = TransactionBuilder::new(&executor)
+        .call_method(component, "approve", vec!["0".to_string(), format!("1,{:?}", badge_resource_def)], Some(account1))
+        .build(vec![key1])
+        .unwrap();
+    assert!(executor.run(transaction4, false).unwrap().success);
+    let transaction5 = TransactionBuilder::new(&executor)
+        .call_method(component, "approve", vec!["0".to_string(), format!("1,{:?}", badge_resource_def)], Some(account1))
+        .build(vec![key1])
+        .unwrap();
+    assert!(!executor.run(transaction5, false).unwrap().success);
+
+    // Account 1 changes their mind
+    let transaction6 = TransactionBuilder::new(&executor)
+        .call_method(component, "revoke", vec!["0".to_string(), format!("1,{:?}", badge_resource_def)], Some(account1))
+        .build(vec![key1])
+        .unwrap();
+    assert!(executor.run(transaction6, false).unwrap().success);
+
+    // An executed proposal can't be revoked anymore
+    let transaction7 = TransactionBuilder::new(&executor)
+        .call_method(component, "revoke", vec!["1".to_string(), format!("1,{:?}", badge_resource_def)], Some(account1))
+        .build(vec![key1])
+        .unwrap();
+    assert!(!executor.run(transaction7, false).unwrap().success);
+
+    // The proposal has expired
+    executor.set_current_epoch(11);
+    let transaction8 = TransactionBuilder::new(&executor)
+        .call_method(component, "approve", vec!["0".to_string(), format!("1,{:?}", badge_resource_def)], Some(account1))
+        .build(vec![key1])
+        .unwrap();
+    let receipt8 = executor.run(transaction8, false).unwrap();
+    println!("{:?}\n", receipt8);
+    assert!(!receipt8.success);
+}
+
+#[test]
+fn test_governance() {
+    // Set up environment.
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut executor = TransactionExecutor::new(&mut ledger, 0, 0);
+
+    let key1 = executor.new_public_key();
+    let account1 = executor.new_account(key1);
+    let key2 = executor.new_public_key();
+    let account2 = executor.new_account(key2);
+    let key3 = executor.new_public_key();
+    let account3 = executor.new_account(key3);
+
+    let package = executor.publish_package(include_code!("multisig_transmission_maker"));
+
+    let transaction1 = TransactionBuilder::new(&executor)
+        .call_function(package, "MultiSigMaker", "new", vec!["2".to_string(), "2".to_string()], Some(account1))
+        .deposit_all_buckets(account1)
+        .build(vec![key1])
+        .unwrap();
+    let receipt1 = executor.run(transaction1, false).unwrap();
+    assert!(receipt1.success);
+    let badge_resource_def = receipt1.resource_def(1).unwrap();
+    let component = receipt1.component(0).unwrap();
+    let badge = format!("1,{}", badge_resource_def);
+
+    // Send one badge to account 2
+    let send_badge = TransactionBuilder::new(&executor)
+        .call_method(account1, "withdraw", vec!["1".to_string(), format!("{}", badge_resource_def)], Some(account1))
+        .deposit_all_buckets(account2)
+        .build(vec![key1])
+        .unwrap();
+    executor.run(send_badge, false).unwrap();
+
+    // At least one signature must be required
+    assert!(!call_method(&mut executor, component, "propose_change_threshold", vec!["0".to_string(), "10".to_string(), badge.clone()], account1, key1));
+
+    // The threshold can't exceed the number of signers once the proposal is executed
+    assert!(call_method(&mut executor, component, "propose_change_threshold", vec!["3".to_string(), "10".to_string(), badge.clone()], account1, key1));
+    assert!(call_method(&mut executor, component, "approve", vec!["0".to_string(), badge.clone()], account1, key1));
+    assert!(!call_method(&mut executor, component, "approve", vec!["0".to_string(), badge.clone()], account2, key2));
+
+    // Propose to add account 3 as a signer and to require all three signatures, then approve both in that order
+    assert!(call_method(&mut executor, component, "propose_add_signer", vec![format!("{}", account3), "10".to_string(), badge.clone()], account1, key1));
+    assert!(call_method(&mut executor, component, "propose_change_threshold", vec!["3".to_string(), "10".to_string(), badge.clone()], account1, key1));
+    for proposal_id in ["1", "2"] {
+        for (account, key) in [(account1, key1), (account2, key2)] {
+            assert!(call_method(&mut executor, component, "approve", vec![proposal_id.to_string(), badge.clone()], account, key));
+        }
+    }
+
+    // Removing a signer would leave fewer signers than the threshold
+    assert!(call_method(&mut executor, component, "propose_remove_signer", vec!["2".to_string(), "10".to_string(), badge.clone()], account3, key3));
+    for (account, key) in [(account1, key1), (account2, key2)] {
+        assert!(call_method(&mut executor, component, "approve", vec!["3".to_string(), badge.clone()], account, key));
+    }
+    assert!(!call_method(&mut executor, component, "approve", vec!["3".to_string(), badge.clone()], account3, key3));
+
+    // Lower the threshold first, then the removal goes through
+    assert!(call_method(&mut executor, component, "propose_change_threshold", vec!["2".to_string(), "10".to_string(), badge.clone()], account1, key1));
+    for (account, key) in [(account1, key1), (account2, key2), (account3, key3)] {
+        assert!(call_method(&mut executor, component, "approve", vec!["4".to_string(), badge.clone()], account, key));
+    }
+    assert!(call_method(&mut executor, component, "approve", vec!["3".to_string(), badge.clone()], account3, key3));
+
+    // The badge of the removed signer can't be used anymore
+    assert!(!call_method(&mut executor, component, "propose_add_signer", vec![format!("{}", account3), "10".to_string(), badge.clone()], account3, key3));
+}
+
+fn call_method<L: Ledger>(executor: &mut TransactionExecutor<L>, component: Address, method: &str, args: Vec<String>, account: Address, key: Address) -> bool {
+    let transaction = TransactionBuilder::new(executor)
+        .call_method(component, method, args, Some(account))
+        .drop_all_bucket_refs()
+        .deposit_all_buckets(account)
+        .build(vec![key])
+        .unwrap();
+    let receipt = executor.run(transaction, false).unwrap();
+    println!("{:?}\n", receipt);
+    receipt.success
+}
+
+// Returns the amount of a resource held in the vaults of a component, like an account or the multisig
+fn get_balance<L: Ledger>(executor: &TransactionExecutor<L>, component: Address, token: Address) -> Decimal {
+    let ledger = executor.ledger();
+    let component_state = ledger.get_component(component).unwrap();
+    let mut vaults = vec![];
+    radix_engine::utils::format_data_with_ledger(
+        component_state.state(radix_engine::model::Actor::SuperUser).unwrap(),
+        ledger,
+        &mut vaults,
+    )
+    .unwrap();
+
+    let mut balance = Decimal::zero();
+    for vid in vaults {
+        let vault = ledger.get_vault(vid).unwrap();
+        if vault.resource_address(radix_engine::model::Actor::SuperUser).unwrap() == token {
+            balance += vault.amount(radix_engine::model::Actor::SuperUser).unwrap();
+        }
+    }
+    balance
 }
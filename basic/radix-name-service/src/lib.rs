@@ -1,20 +1,138 @@
+use sbor::*;
 use scrypto::prelude::*;
 use sha2::{Digest, Sha256};
 
+/// A typed record that a name resolves to, in addition to its address
+#[derive(TypeId, Encode, Decode, Describe, Clone)]
+enum Record {
+    ComponentAddress(Address),
+    ResourceAddress(Address),
+    Text(String),
+    PublicKey(Address),
+}
+
 #[derive(NftData)]
 struct DomainName {
+    name: String,
+    // The ID of the parent domain registration, if this is a subdomain
+    parent_id: Option<u128>,
     #[scrypto(mutable)]
     address: Address,
+    // Subdomains expire together with their parent domain
     #[scrypto(mutable)]
     last_valid_epoch: u64,
     #[scrypto(mutable)]
     deposit_amount: Decimal,
+    // At most one record of every type
+    #[scrypto(mutable)]
+    records: Vec<Record>,
+}
+
+/// Given to the seller of a listed name, to change the price, accept offers and close the listing
+#[derive(NftData)]
+struct ListingReceipt {
+    name: String,
+}
+
+/// Given to a buyer that made an offer for a listed name, to claim the name or withdraw the offer
+#[derive(NftData)]
+struct OfferReceipt {
+    name: String,
+    amount: Decimal,
+}
+
+#[derive(TypeId, Encode, Decode)]
+struct Listing {
+    // The ID of the listed DomainName NFT
+    name_id: u128,
+    price: Decimal,
+    // The payment minus the royalty, once the name has been sold
+    proceeds: Option<Decimal>,
+}
+
+#[derive(TypeId, Encode, Decode)]
+struct Offer {
+    listing_id: u128,
+    // The ID of the DomainName NFT the offer was made for
+    name_id: u128,
+    amount: Decimal,
+    accepted: bool,
+    // Set once the offer has been withdrawn or the name claimed
+    closed: bool,
 }
 
+// Names with up to this many characters (without the '.xrd') are charged a premium
+const SHORT_NAME_MAX_LENGTH: usize = 4;
+
 // Assuming an average epoch duration of 35 mninutes, 15k epochs very roughly fit into one year
 // This is just a very rough estimate, of course
 const EPOCHS_PER_YEAR: u64 = 15_000;
 
+// After a name expires, only its owner can renew it for roughly another three months.
+// Afterwards anyone may register the name again.
+const GRACE_PERIOD_EPOCHS: u64 = EPOCHS_PER_YEAR / 4;
+
+// The same limits as for DNS names
+const MAX_LABEL_LENGTH: usize = 63;
+const MAX_NAME_LENGTH: usize = 253;
+
+/// Returns the canonical form of the given `name` (e.g. `Foo.xrd` becomes `foo.xrd`), which is the form that is hashed.
+/// Surrounding whitespace is removed and letters are lowercased. Every label must only consist of the characters
+/// `a-z`, `0-9` and `-`, must not start or end with a `-` and must be 1 to 63 characters long. The name must end on `.xrd`.
+/// Non-ASCII characters are rejected, so that names can't be imitated with homoglyphs.
+fn normalize_name(name: &str) -> Result<String, String> {
+    let name = name.trim().to_ascii_lowercase();
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "The domain name must not be longer than {} characters",
+            MAX_NAME_LENGTH
+        ));
+    }
+    let labels = name
+        .strip_suffix(".xrd")
+        .ok_or_else(|| "The domain name must end on '.xrd'".to_string())?;
+    for label in labels.split('.') {
+        check_label(label)?;
+    }
+    Ok(name)
+}
+
+/// Returns the canonical form of the given subdomain `label` (e.g. `Pay` becomes `pay`).
+/// The label is validated like every label of a name by `normalize_name`.
+fn normalize_label(label: &str) -> Result<String, String> {
+    let label = label.trim().to_ascii_lowercase();
+    check_label(&label)?;
+    Ok(label)
+}
+
+fn check_label(label: &str) -> Result<(), String> {
+    if label.is_empty() {
+        return Err("The labels of a domain name must not be empty".to_string());
+    }
+    if label.len() > MAX_LABEL_LENGTH {
+        return Err(format!(
+            "The label '{}' must not be longer than {} characters",
+            label, MAX_LABEL_LENGTH
+        ));
+    }
+    if let Some(c) = label
+        .chars()
+        .find(|c| !c.is_ascii_lowercase() && !c.is_ascii_digit() && *c != '-')
+    {
+        return Err(format!(
+            "The label '{}' contains the invalid character {:?}. Only a-z, 0-9 and '-' are allowed",
+            label, c
+        ));
+    }
+    if label.starts_with('-') || label.ends_with('-') {
+        return Err(format!(
+            "The label '{}' must not start or end with a '-'",
+            label
+        ));
+    }
+    Ok(())
+}
+
 blueprint! {
 
     struct RadixNameService {
@@ -26,6 +144,33 @@ blueprint! {
         deposit_per_year: Decimal,
         fee_address_update: Decimal,
         fee_renewal_per_year: Decimal,
+        // The yearly premium for names with 1 to SHORT_NAME_MAX_LENGTH characters, indexed by the length minus one
+        short_name_premiums_per_year: Vec<Decimal>,
+        // The hashes of the names that can't be registered
+        reserved_names: HashSet<u128>,
+        // Maps the hash of every registered name to the ID of the DomainName NFT of its current registration,
+        // or to None once the name has been unregistered or released
+        registrations: LazyMap<u128, Option<u128>>,
+        // Counts how often each name has been registered, to derive unique NFT IDs for re-registrations
+        registration_counts: LazyMap<u128, u64>,
+        // Maps addresses to the ID of the DomainName NFT of their primary name, or to None once it has been removed
+        primary_names: LazyMap<Address, Option<u128>>,
+        // The percentage of every sale on the marketplace that is added to the fees
+        royalty_percent: Decimal,
+        listing_resource: ResourceDef,
+        offer_resource: ResourceDef,
+        // Holds the DomainName NFTs of listed names until they are sold or the listing is closed
+        listed_names: Vault,
+        // Holds the offered XRD and the proceeds of sold names until they are withdrawn
+        marketplace_payments: Vault,
+        // Indexed by the ID of the ListingReceipt NFTs
+        listings: LazyMap<u128, Listing>,
+        // Maps the ID of every listed DomainName NFT to the ID of its open listing, or to None once it has been sold or closed
+        open_listings: LazyMap<u128, Option<u128>>,
+        // Indexed by the ID of the OfferReceipt NFTs
+        offers: LazyMap<u128, Offer>,
+        next_listing_id: u128,
+        next_offer_id: u128,
     }
 
     impl RadixNameService {
@@ -43,15 +188,42 @@ blueprint! {
                 .badge(minter.resource_def(), ALL_PERMISSIONS)
                 .no_initial_supply();
 
+            let listing_resource = ResourceBuilder::new_non_fungible()
+                .metadata("name", "DomainName Listing")
+                .flags(MINTABLE | BURNABLE)
+                .badge(minter.resource_def(), MAY_MINT | MAY_BURN)
+                .no_initial_supply();
+
+            let offer_resource = ResourceBuilder::new_non_fungible()
+                .metadata("name", "DomainName Offer")
+                .flags(MINTABLE | BURNABLE)
+                .badge(minter.resource_def(), MAY_MINT | MAY_BURN)
+                .no_initial_supply();
+
             let component = RadixNameService {
                 admin_badge: admin_badge.resource_def(),
                 minter: Vault::with_bucket(minter),
+                listed_names: Vault::new(name_resource.address()),
                 name_resource,
                 deposits: Vault::new(RADIX_TOKEN),
                 fees: Vault::new(RADIX_TOKEN),
                 deposit_per_year: 50.into(),
                 fee_address_update: 10.into(),
                 fee_renewal_per_year: 25.into(),
+                short_name_premiums_per_year: vec![500.into(), 250.into(), 100.into(), 50.into()],
+                reserved_names: HashSet::new(),
+                registrations: LazyMap::new(),
+                registration_counts: LazyMap::new(),
+                primary_names: LazyMap::new(),
+                royalty_percent: 5.into(),
+                listing_resource,
+                offer_resource,
+                marketplace_payments: Vault::new(RADIX_TOKEN),
+                listings: LazyMap::new(),
+                open_listings: LazyMap::new(),
+                offers: LazyMap::new(),
+                next_listing_id: 0,
+                next_offer_id: 0,
             }
             .instantiate();
 
@@ -59,25 +231,53 @@ blueprint! {
         }
 
         /// Lookup the address for a given `name`.
-        /// Panics if that name is not registered.
+        /// Panics if that name is not registered or has expired.
         pub fn lookup_address(&self, name: String) -> Address {
-            let hash = Self::hash_name(name);
-            let name_data: DomainName = self.name_resource.get_nft_data(hash);
-            name_data.address
+            self.get_valid_name_data(Self::normalize(&name)).address
+        }
+
+        /// Lookup the record of the given `record_type` for a given `name`.
+        /// The record type is one of `component`, `resource`, `text` or `public_key`.
+        /// Returns None if the name has no such record and panics if that name is not registered or has expired.
+        pub fn lookup_record(&self, name: String, record_type: String) -> Option<Record> {
+            Self::check_record_type(&record_type);
+            self.get_valid_name_data(Self::normalize(&name))
+                .records
+                .into_iter()
+                .find(|record| Self::record_type(record) == record_type)
+        }
+
+        /// Lookup the primary name of the given `address`.
+        /// Returns None if the address has no primary name, or the name no longer maps to the address or has expired.
+        pub fn reverse_lookup(&self, address: Address) -> Option<String> {
+            let id = self.primary_names.get(&address).flatten()?;
+            let name_data = self.name_resource.get_nft_data::<DomainName>(id);
+            let last_valid_epoch = self.get_last_valid_epoch(id, &name_data.name)?;
+            if Context::current_epoch() > last_valid_epoch || name_data.address != address {
+                return None;
+            }
+            Some(name_data.name)
         }
 
         /// Registers the given `name` and maps it to the given `target_address` for `reserve_years`.
         /// The supplied `deposit` is locked until the name is unregistered.
+        /// Short names are charged a yearly premium on top of the deposit, which is not returned when the name is unregistered.
+        /// Names whose grace period has passed may be registered again by anyone. The previous holder
+        /// can still unregister their DomainName NFT to get their deposit back.
         ///
         /// This method returns an NFT that represents ownership of the registered name and any overpaid deposit.
         pub fn register_name(
-            &self,
+            &mut self,
             name: String,
             target_address: Address,
             reserve_years: u8,
             deposit: Bucket,
         ) -> (Bucket, Bucket) {
-            assert!(name.ends_with(".xrd"), "The domain name must end on '.xrd'");
+            let name = Self::normalize(&name);
+            assert!(
+                name.matches('.').count() == 1,
+                "Subdomains must be registered by the owner of their parent domain"
+            );
             assert!(
                 reserve_years > 0,
                 "A name must be reserved for at least one year"
@@ -87,36 +287,96 @@ blueprint! {
                 "The deposit must be made in XRD"
             );
 
-            let hash = Self::hash_name(name);
+            assert!(
+                !self.reserved_names.contains(&Self::hash_name(name.clone())),
+                "The name is reserved"
+            );
+            self.check_available(&name);
+
             let deposit_amount = self.deposit_per_year * Decimal::from(reserve_years);
+            let premium_amount = self.premium_per_year(&name) * Decimal::from(reserve_years);
             let last_valid_epoch =
                 Context::current_epoch() + EPOCHS_PER_YEAR * u64::from(reserve_years);
 
             assert!(
-                deposit.amount() >= deposit_amount,
-                "Insufficient deposit. You need to send a deposit of {} XRD",
-                deposit_amount
+                deposit.amount() >= deposit_amount + premium_amount,
+                "Insufficient deposit. You need to send a deposit of {} XRD and a premium of {} XRD",
+                deposit_amount,
+                premium_amount
             );
 
+            let id = self.register_nft_id(&name);
             let name_data = DomainName {
+                name,
+                parent_id: None,
                 address: target_address,
                 last_valid_epoch,
                 deposit_amount,
+                records: Vec::new(),
             };
 
             let name_nft = self
                 .minter
-                .authorize(|auth| self.name_resource.mint_nft(hash, name_data, auth));
+                .authorize(|auth| self.name_resource.mint_nft(id, name_data, auth));
 
             self.deposits.put(deposit.take(deposit_amount));
+            self.fees.put(deposit.take(premium_amount));
 
             (name_nft, deposit)
         }
 
+        /// Registers the subdomain `label` of the name that is represented by the given `parent_nft`
+        /// (e.g. `pay` for `pay.foo.xrd`) and maps it to the given `target_address`.
+        /// Subdomains require no deposit and stay valid as long as the registration of their parent domain.
+        ///
+        /// This method returns an NFT that represents ownership of the subdomain. It may be transferred to delegate the subdomain.
+        pub fn register_subdomain(
+            &mut self,
+            parent_nft: BucketRef,
+            label: String,
+            target_address: Address,
+        ) -> Bucket {
+            assert!(
+                parent_nft.resource_address() == self.name_resource.address(),
+                "The parent_nft bucket does not represent a domain name NFT"
+            );
+            assert!(
+                parent_nft.amount() == Decimal::one(),
+                "The parent_nft bucket must contain exactly one DomainName NFT"
+            );
+            let label = normalize_label(&label).unwrap_or_else(|error| panic!("{}", error));
+
+            let parent_id = parent_nft.get_nft_id();
+            let parent_data = self.get_owned_name_data(parent_id);
+            let name = format!("{}.{}", label, parent_data.name);
+            assert!(
+                name.len() <= MAX_NAME_LENGTH,
+                "The domain name must not be longer than {} characters",
+                MAX_NAME_LENGTH
+            );
+            self.check_available(&name);
+
+            let id = self.register_nft_id(&name);
+            let name_data = DomainName {
+                name,
+                parent_id: Some(parent_id),
+                address: target_address,
+                last_valid_epoch: parent_data.last_valid_epoch,
+                deposit_amount: Decimal::zero(),
+                records: Vec::new(),
+            };
+            let name_nft = self
+                .minter
+                .authorize(|auth| self.name_resource.mint_nft(id, name_data, auth));
+
+            parent_nft.drop();
+            name_nft
+        }
+
         /// Unregister the name(s) that is/are represented by the given `name_nft` bucket.
         /// Returns a bucket with the tokens that were initially deposited when the name(s) was/were registered.
         /// The supplied `name_nft` is burned.
-        pub fn unregister_name(&self, name_nft: Bucket) -> Bucket {
+        pub fn unregister_name(&mut self, name_nft: Bucket) -> Bucket {
             assert!(
                 name_nft.resource_address() == self.name_resource.address(),
                 "The supplied bucket does not represent a domain name NFT"
@@ -126,6 +386,13 @@ blueprint! {
             let mut total_deposit_amount = Decimal::zero();
             for nft in name_nft.get_nfts::<DomainName>() {
                 total_deposit_amount += nft.data().deposit_amount;
+                self.clear_primary_name(nft.data().address, nft.id());
+
+                // The name may have been registered again by someone else after it expired
+                let hash = Self::hash_name(nft.data().name);
+                if self.get_registration(hash) == Some(nft.id()) {
+                    self.registrations.insert(hash, None);
+                }
             }
 
             self.minter.authorize(|auth| name_nft.burn_with_auth(auth));
@@ -136,7 +403,7 @@ blueprint! {
         /// The fee is not added to the initial deposit and is not returned when the name is unregistered.
         /// Returns any overpaid fees.
         pub fn update_address(
-            &self,
+            &mut self,
             name_nft: BucketRef,
             new_address: Address,
             fee: Bucket,
@@ -162,14 +429,110 @@ blueprint! {
             );
 
             let id = name_nft.get_nft_id();
-            let old_name_data = self.name_resource.get_nft_data::<DomainName>(id);
-            let new_name_data = DomainName {
-                address: new_address,
-                last_valid_epoch: old_name_data.last_valid_epoch,
-                deposit_amount: old_name_data.deposit_amount,
-            };
+            let mut name_data = self.get_owned_name_data(id);
+            self.clear_primary_name(name_data.address, id);
+            name_data.address = new_address;
+            self.minter
+                .authorize(|auth| self.name_resource.update_nft_data(id, name_data, auth));
+
+            self.fees.put(fee.take(fee_amount));
+
+            name_nft.drop();
+            fee
+        }
+
+        /// Sets the name that is represented by the given `name_nft` as the primary name of the address it maps to.
+        /// If the address is a public key, the transaction must be signed with that key.
+        /// Any previous primary name of the address is replaced, so the owner of the address can always set the name they want.
+        /// The primary name no longer resolves once the address of the name is updated, or the name is unregistered or expires.
+        pub fn set_primary_name(&mut self, name_nft: BucketRef) {
+            assert!(
+                name_nft.resource_address() == self.name_resource.address(),
+                "The name_nft bucket does not represent a domain name NFT"
+            );
+            assert!(
+                name_nft.amount() == Decimal::one(),
+                "The name_nft bucket must contain exactly one DomainName NFT"
+            );
+
+            let id = name_nft.get_nft_id();
+            let name_data = self.get_owned_name_data(id);
+            if let Address::PublicKey(_) = name_data.address {
+                assert!(
+                    Context::transaction_signers().contains(&name_data.address),
+                    "Only the owner of the public key can set its primary name"
+                );
+            }
+            self.primary_names.insert(name_data.address, Some(id));
+
+            name_nft.drop();
+        }
+
+        /// Unsets the name that is represented by the given `name_nft` as the primary name of the address it maps to.
+        pub fn unset_primary_name(&mut self, name_nft: BucketRef) {
+            assert!(
+                name_nft.resource_address() == self.name_resource.address(),
+                "The name_nft bucket does not represent a domain name NFT"
+            );
+            assert!(
+                name_nft.amount() == Decimal::one(),
+                "The name_nft bucket must contain exactly one DomainName NFT"
+            );
+
+            let id = name_nft.get_nft_id();
+            let name_data = self.name_resource.get_nft_data::<DomainName>(id);
+            assert!(
+                self.primary_names.get(&name_data.address).flatten() == Some(id),
+                "The name is not the primary name of its address"
+            );
+            self.clear_primary_name(name_data.address, id);
+
+            name_nft.drop();
+        }
+
+        /// Sets the record of the given `record_type` for the name that is represented by the given `name_nft`,
+        /// replacing any previous record of that type. An empty `value` removes the record.
+        /// The record type is one of `component`, `resource`, `text` or `public_key`.
+        /// The fee is the same as for updating the address and is not returned when the name is unregistered.
+        /// Returns any overpaid fees.
+        pub fn set_record(
+            &self,
+            name_nft: BucketRef,
+            record_type: String,
+            value: String,
+            fee: Bucket,
+        ) -> Bucket {
+            assert!(
+                name_nft.resource_address() == self.name_resource.address(),
+                "The name_nft bucket does not represent a domain name NFT"
+            );
+            assert!(
+                name_nft.amount() == Decimal::one(),
+                "The name_nft bucket must contain exactly one DomainName NFT"
+            );
+            assert!(
+                fee.resource_address() == RADIX_TOKEN,
+                "The fee must be payed in XRD"
+            );
+            Self::check_record_type(&record_type);
+
+            let fee_amount = self.fee_address_update;
+            assert!(
+                fee.amount() >= fee_amount,
+                "Insufficient fee amount. You need to send a fee of {} XRD",
+                fee_amount
+            );
+
+            let id = name_nft.get_nft_id();
+            let mut name_data = self.get_owned_name_data(id);
+            name_data
+                .records
+                .retain(|record| Self::record_type(record) != record_type);
+            if !value.is_empty() {
+                name_data.records.push(Self::parse_record(&record_type, value));
+            }
             self.minter
-                .authorize(|auth| self.name_resource.update_nft_data(id, new_name_data, auth));
+                .authorize(|auth| self.name_resource.update_nft_data(id, name_data, auth));
 
             self.fees.put(fee.take(fee_amount));
 
@@ -178,6 +541,8 @@ blueprint! {
         }
 
         /// Renews the name identified by the given `name_nft` for `renew_years`.
+        /// An expired name can only be renewed during its grace period.
+        /// Short names are charged their yearly premium in addition to the renewal fee.
         /// The fee is not added to the initial deposit and is not returned when the name is unregistered.
         /// Returns any overpaid fees.
         pub fn renew_name(&self, name_nft: BucketRef, renew_years: u8, fee: Bucket) -> Bucket {
@@ -189,6 +554,316 @@ blueprint! {
                 name_nft.amount() == Decimal::one(),
                 "The supplied bucket must contain exactly one DomainName NFT"
             );
+
+            let fee = self.renew(name_nft.get_nft_id(), renew_years, fee);
+            name_nft.drop();
+            fee
+        }
+
+        /// Releases the names of the DomainName NFTs with the given `ids` whose grace period has passed,
+        /// and removes them as the primary names of the addresses they map to.
+        /// IDs of NFTs that are not the current registration of their name, or are still in their grace period, are skipped.
+        /// The IDs must belong to DomainName NFTs that have not been unregistered.
+        /// The DomainName NFTs can't be burned until resources can be recalled from vaults,
+        /// but their holders can still unregister them to get their deposits back.
+        #[auth(admin_badge)]
+        pub fn burn_expired_names(&mut self, ids: Vec<u128>) {
+            let current_epoch = Context::current_epoch();
+            for id in ids {
+                let name_data = self.name_resource.get_nft_data::<DomainName>(id);
+                let hash = Self::hash_name(name_data.name.clone());
+                if self.get_registration(hash) != Some(id) {
+                    continue;
+                }
+                // Subdomains are released together with their parent domain
+                let expired = self
+                    .get_last_valid_epoch(id, &name_data.name)
+                    .map_or(true, |last_valid_epoch| {
+                        current_epoch > last_valid_epoch + GRACE_PERIOD_EPOCHS
+                    });
+                if expired {
+                    self.registrations.insert(hash, None);
+                    self.clear_primary_name(name_data.address, id);
+                }
+            }
+        }
+
+        /// Lists the name that is represented by the given `name_nft` for sale at the given `price` in XRD.
+        /// The DomainName NFT is held by the service until the name is sold or the listing is closed.
+        /// Its data, including the expiry and the deposit, is passed on to the buyer unchanged.
+        ///
+        /// This method returns a ListingReceipt NFT that is needed to change the price, accept offers and close the listing.
+        pub fn list_name(&mut self, name_nft: Bucket, price: Decimal) -> Bucket {
+            assert!(
+                name_nft.resource_address() == self.name_resource.address(),
+                "The name_nft bucket does not represent a domain name NFT"
+            );
+            assert!(
+                name_nft.amount() == Decimal::one(),
+                "The name_nft bucket must contain exactly one DomainName NFT"
+            );
+            assert!(price > Decimal::zero(), "The price must be positive");
+
+            let name_id = name_nft.get_nft_id();
+            let name_data = self.get_owned_name_data(name_id);
+            self.listed_names.put(name_nft);
+
+            let listing_id = self.next_listing_id;
+            self.next_listing_id += 1;
+            self.listings.insert(
+                listing_id,
+                Listing {
+                    name_id,
+                    price,
+                    proceeds: None,
+                },
+            );
+            self.open_listings.insert(name_id, Some(listing_id));
+
+            let receipt = ListingReceipt {
+                name: name_data.name,
+            };
+            self.minter
+                .authorize(|auth| self.listing_resource.mint_nft(listing_id, receipt, auth))
+        }
+
+        /// Changes the price of the listing that is represented by the supplied ListingReceipt NFT.
+        #[auth(listing_resource)]
+        pub fn update_price(&mut self, price: Decimal) {
+            assert!(price > Decimal::zero(), "The price must be positive");
+            let listing_id = Self::get_receipt_id(&auth);
+            self.get_open_listing(listing_id);
+            let mut listing = self.listings.get(&listing_id).unwrap();
+            listing.price = price;
+            self.listings.insert(listing_id, listing);
+        }
+
+        /// Renews the name of the listing that is represented by the supplied ListingReceipt NFT for `renew_years`,
+        /// with the same fees as `renew_name`. Returns any overpaid fees.
+        #[auth(listing_resource)]
+        pub fn renew_listed_name(&self, renew_years: u8, fee: Bucket) -> Bucket {
+            let listing_id = Self::get_receipt_id(&auth);
+            let (name_id, _) = self.get_open_listing(listing_id);
+            self.renew(name_id, renew_years, fee)
+        }
+
+        /// Buys the listed `name` for its price. The royalty is taken from the price and the rest
+        /// is paid out to the seller when they close the listing.
+        ///
+        /// This method returns the DomainName NFT and any overpaid XRD.
+        pub fn purchase_name(&mut self, name: String, payment: Bucket) -> (Bucket, Bucket) {
+            assert!(
+                payment.resource_address() == RADIX_TOKEN,
+                "The payment must be made in XRD"
+            );
+            let listing_id = self.find_listing(&Self::normalize(&name));
+            let (name_id, price) = self.get_open_listing(listing_id);
+            self.check_resolves(name_id);
+            assert!(
+                payment.amount() >= price,
+                "Insufficient payment. The name costs {} XRD",
+                price
+            );
+
+            self.marketplace_payments.put(payment.take(price));
+            self.sell(listing_id, price);
+
+            (self.listed_names.take_nft(name_id), payment)
+        }
+
+        /// Offers the XRD in the given `payment` for the listed `name`.
+        /// The payment is held by the service until the offer is accepted or withdrawn.
+        ///
+        /// This method returns an OfferReceipt NFT that is needed to claim the name once the offer has been accepted,
+        /// or to withdraw the offer.
+        pub fn make_offer(&mut self, name: String, payment: Bucket) -> Bucket {
+            assert!(
+                payment.resource_address() == RADIX_TOKEN,
+                "The payment must be made in XRD"
+            );
+            assert!(!payment.is_empty(), "The payment must not be empty");
+            let name = Self::normalize(&name);
+            let listing_id = self.find_listing(&name);
+            let (name_id, _) = self.get_open_listing(listing_id);
+            self.check_resolves(name_id);
+
+            let amount = payment.amount();
+            self.marketplace_payments.put(payment);
+
+            let offer_id = self.next_offer_id;
+            self.next_offer_id += 1;
+            self.offers.insert(
+                offer_id,
+                Offer {
+                    listing_id,
+                    name_id,
+                    amount,
+                    accepted: false,
+                    closed: false,
+                },
+            );
+
+            let receipt = OfferReceipt { name, amount };
+            self.minter
+                .authorize(|auth| self.offer_resource.mint_nft(offer_id, receipt, auth))
+        }
+
+        /// Sells the name of the listing that is represented by the supplied ListingReceipt NFT to the offer with the given `offer_id`.
+        /// The buyer claims the name with their OfferReceipt NFT, all other offers can only be withdrawn.
+        #[auth(listing_resource)]
+        pub fn accept_offer(&mut self, offer_id: u128) {
+            let listing_id = Self::get_receipt_id(&auth);
+            let (name_id, _) = self.get_open_listing(listing_id);
+            self.check_resolves(name_id);
+
+            let mut offer = self.offers.get(&offer_id).expect("The offer does not exist");
+            assert!(
+                offer.listing_id == listing_id,
+                "The offer was not made for this listing"
+            );
+            assert!(!offer.closed, "The offer has been withdrawn");
+            offer.accepted = true;
+            let amount = offer.amount;
+            self.offers.insert(offer_id, offer);
+
+            self.sell(listing_id, amount);
+        }
+
+        /// Closes the listing that is represented by the given `listing_receipt`, which is burned.
+        /// Returns the proceeds if the name has been sold, or the DomainName NFT otherwise.
+        pub fn close_listing(&mut self, listing_receipt: Bucket) -> Bucket {
+            assert!(
+                listing_receipt.resource_address() == self.listing_resource.address(),
+                "The listing_receipt bucket does not represent a ListingReceipt NFT"
+            );
+            assert!(
+                listing_receipt.amount() == Decimal::one(),
+                "The listing_receipt bucket must contain exactly one ListingReceipt NFT"
+            );
+
+            let listing = self
+                .listings
+                .get(&listing_receipt.get_nft_id())
+                .expect("The listing does not exist");
+            self.minter
+                .authorize(|auth| listing_receipt.burn_with_auth(auth));
+
+            match listing.proceeds {
+                Some(proceeds) => self.marketplace_payments.take(proceeds),
+                None => {
+                    self.open_listings.insert(listing.name_id, None);
+                    self.listed_names.take_nft(listing.name_id)
+                }
+            }
+        }
+
+        /// Closes the offer that is represented by the given `offer_receipt`, which is burned.
+        /// Returns the DomainName NFT if the offer has been accepted, or the offered XRD otherwise.
+        pub fn close_offer(&mut self, offer_receipt: Bucket) -> Bucket {
+            assert!(
+                offer_receipt.resource_address() == self.offer_resource.address(),
+                "The offer_receipt bucket does not represent an OfferReceipt NFT"
+            );
+            assert!(
+                offer_receipt.amount() == Decimal::one(),
+                "The offer_receipt bucket must contain exactly one OfferReceipt NFT"
+            );
+
+            let offer_id = offer_receipt.get_nft_id();
+            let mut offer = self.offers.get(&offer_id).expect("The offer does not exist");
+            let tokens = if offer.accepted {
+                self.listed_names.take_nft(offer.name_id)
+            } else {
+                self.marketplace_payments.take(offer.amount)
+            };
+            offer.closed = true;
+            self.offers.insert(offer_id, offer);
+            self.minter
+                .authorize(|auth| offer_receipt.burn_with_auth(auth));
+
+            tokens
+        }
+
+        /// Sets the deposit and fees that are charged for new registrations, address and record updates and renewals.
+        #[auth(admin_badge)]
+        pub fn set_fee_schedule(
+            &mut self,
+            deposit_per_year: Decimal,
+            fee_address_update: Decimal,
+            fee_renewal_per_year: Decimal,
+        ) {
+            assert!(
+                deposit_per_year >= Decimal::zero()
+                    && fee_address_update >= Decimal::zero()
+                    && fee_renewal_per_year >= Decimal::zero(),
+                "The deposit and fees must not be negative"
+            );
+            self.deposit_per_year = deposit_per_year;
+            self.fee_address_update = fee_address_update;
+            self.fee_renewal_per_year = fee_renewal_per_year;
+        }
+
+        /// Sets the yearly premium for names with the given `length` (without the '.xrd'), which must be 1 to 4 characters.
+        #[auth(admin_badge)]
+        pub fn set_short_name_premium(&mut self, length: u8, premium_per_year: Decimal) {
+            assert!(
+                length > 0 && usize::from(length) <= SHORT_NAME_MAX_LENGTH,
+                "Only names with 1 to {} characters are charged a premium",
+                SHORT_NAME_MAX_LENGTH
+            );
+            assert!(
+                premium_per_year >= Decimal::zero(),
+                "The premium must not be negative"
+            );
+            self.short_name_premiums_per_year[usize::from(length) - 1] = premium_per_year;
+        }
+
+        /// Reserves the given `name`, so that it can no longer be registered.
+        /// Names that are already registered stay valid until they are unregistered or expire.
+        #[auth(admin_badge)]
+        pub fn reserve_name(&mut self, name: String) {
+            self.reserved_names.insert(Self::hash_name(Self::normalize(&name)));
+        }
+
+        /// Releases a name that was reserved with `reserve_name`.
+        #[auth(admin_badge)]
+        pub fn unreserve_name(&mut self, name: String) {
+            assert!(
+                self.reserved_names.remove(&Self::hash_name(Self::normalize(&name))),
+                "The name is not reserved"
+            );
+        }
+
+        /// Sets the percentage of every sale on the marketplace that is added to the fees.
+        #[auth(admin_badge)]
+        pub fn set_royalty(&mut self, royalty_percent: Decimal) {
+            assert!(
+                royalty_percent >= Decimal::zero() && royalty_percent <= 100.into(),
+                "The royalty must be between 0 and 100 percent"
+            );
+            self.royalty_percent = royalty_percent;
+        }
+
+        /// Withdraws all fees that have been collected.
+        /// Deposits stay locked until the names are unregistered.
+        #[auth(admin_badge)]
+        pub fn withdraw_fees(&mut self) -> Bucket {
+            self.fees.take_all()
+        }
+
+        /// Returns the yearly premium for the given top level `name`, which is zero unless the name is short.
+        fn premium_per_year(&self, name: &str) -> Decimal {
+            let length = name.trim_end_matches(".xrd").chars().count();
+            if length > 0 && length <= SHORT_NAME_MAX_LENGTH {
+                self.short_name_premiums_per_year[length - 1]
+            } else {
+                Decimal::zero()
+            }
+        }
+
+        /// Renews the name of the DomainName NFT with the given `id` for `renew_years`, see `renew_name`.
+        /// Returns any overpaid fees.
+        fn renew(&self, id: u128, renew_years: u8, fee: Bucket) -> Bucket {
             assert!(
                 fee.resource_address() == RADIX_TOKEN,
                 "The fee must be payed in XRD"
@@ -198,15 +873,20 @@ blueprint! {
                 "The name must be renewed for at least one year"
             );
 
-            let fee_amount = self.fee_renewal_per_year * renew_years;
+            let mut name_data = self.get_owned_name_data(id);
+            assert!(
+                name_data.parent_id.is_none(),
+                "Subdomains expire together with their parent domain and can't be renewed"
+            );
+
+            let fee_amount =
+                (self.fee_renewal_per_year + self.premium_per_year(&name_data.name)) * renew_years;
             assert!(
                 fee.amount() >= fee_amount,
                 "Insufficient fee amount. You need to send a fee of {} XRD",
                 fee_amount
             );
 
-            let id = name_nft.get_nft_id();
-            let mut name_data = self.name_resource.get_nft_data::<DomainName>(id);
             name_data.last_valid_epoch =
                 name_data.last_valid_epoch + EPOCHS_PER_YEAR * u64::from(renew_years);
             self.minter
@@ -214,17 +894,199 @@ blueprint! {
 
             self.fees.put(fee.take(fee_amount));
 
-            name_nft.drop();
             fee
         }
 
-        // Burns all names that have expired. Must be called regularly.
-        #[auth(admin_badge)]
-        pub fn burn_expired_names(&self) {
-            todo!("This can be implemented as soon as resources can be recalled from vaults")
+        /// Returns the ID of the open listing of the given `name`.
+        fn find_listing(&self, name: &str) -> u128 {
+            let name_id = self
+                .get_registration(Self::hash_name(name.to_string()))
+                .expect("The name is not registered");
+            self.open_listings
+                .get(&name_id)
+                .flatten()
+                .expect("The name is not listed")
+        }
+
+        /// Panics if the listed DomainName NFT with the given `name_id` no longer resolves,
+        /// so that nobody buys an expired name. The seller can still renew it with `renew_listed_name`.
+        fn check_resolves(&self, name_id: u128) {
+            let name_data = self.name_resource.get_nft_data::<DomainName>(name_id);
+            let last_valid_epoch = self
+                .get_last_valid_epoch(name_id, &name_data.name)
+                .expect("The name has been registered by someone else");
+            assert!(
+                Context::current_epoch() <= last_valid_epoch,
+                "The name has expired"
+            );
+        }
+
+        /// Returns the ID of the listed DomainName NFT and the price of the listing with the given `listing_id`.
+        /// Panics if the listing does not exist or the name has already been sold.
+        fn get_open_listing(&self, listing_id: u128) -> (u128, Decimal) {
+            let listing = self
+                .listings
+                .get(&listing_id)
+                .expect("The listing does not exist");
+            assert!(listing.proceeds.is_none(), "The name has already been sold");
+            (listing.name_id, listing.price)
+        }
+
+        /// Marks the listing with the given `listing_id` as sold for the given `amount`, which must already be held
+        /// in the marketplace_payments vault. The royalty is added to the fees and the primary name of the seller is removed.
+        fn sell(&mut self, listing_id: u128, amount: Decimal) {
+            let royalty = amount * self.royalty_percent / 100;
+            self.fees.put(self.marketplace_payments.take(royalty));
+
+            let mut listing = self.listings.get(&listing_id).unwrap();
+            listing.proceeds = Some(amount - royalty);
+            let name_id = listing.name_id;
+            self.listings.insert(listing_id, listing);
+            self.open_listings.insert(name_id, None);
+
+            let name_data = self.get_owned_name_data(name_id);
+            self.clear_primary_name(name_data.address, name_id);
+        }
+
+        /// Returns the ID of the single receipt NFT in the given `receipt`.
+        fn get_receipt_id(receipt: &BucketRef) -> u128 {
+            assert!(
+                receipt.amount() == Decimal::one(),
+                "The receipt must contain exactly one NFT"
+            );
+            receipt.get_nft_id()
+        }
+
+        /// Returns the last epoch in which the name represented by the DomainName NFT with the given `id` is valid.
+        /// Subdomains are valid as long as the registration of the parent domain they were created under.
+        /// Returns None if the NFT (or the registration of a parent domain) no longer represents the current registration of its name.
+        fn get_last_valid_epoch(&self, id: u128, name: &str) -> Option<u64> {
+            if self.get_registration(Self::hash_name(name.to_string())) != Some(id) {
+                return None;
+            }
+
+            let name_data = self.name_resource.get_nft_data::<DomainName>(id);
+            match name_data.parent_id {
+                None => Some(name_data.last_valid_epoch),
+                Some(parent_id) => {
+                    let (_, parent_name) = name.split_once('.').unwrap();
+                    self.get_last_valid_epoch(parent_id, parent_name)
+                }
+            }
+        }
+
+        /// Returns the data of the current registration of the given `name`.
+        /// Panics if that name is not registered or has expired.
+        fn get_valid_name_data(&self, name: String) -> DomainName {
+            let id = self
+                .get_registration(Self::hash_name(name.clone()))
+                .expect("The name is not registered");
+            let last_valid_epoch = self
+                .get_last_valid_epoch(id, &name)
+                .expect("The name is not registered");
+            assert!(
+                Context::current_epoch() <= last_valid_epoch,
+                "The name has expired"
+            );
+            self.name_resource.get_nft_data::<DomainName>(id)
+        }
+
+        /// Returns the data of the DomainName NFT with the given `id`.
+        /// Panics if the NFT no longer represents the current registration of its name or its grace period has passed.
+        fn get_owned_name_data(&self, id: u128) -> DomainName {
+            let name_data = self.name_resource.get_nft_data::<DomainName>(id);
+            let last_valid_epoch = self
+                .get_last_valid_epoch(id, &name_data.name)
+                .expect("The name has been registered by someone else");
+            assert!(
+                Context::current_epoch() <= last_valid_epoch + GRACE_PERIOD_EPOCHS,
+                "The grace period of the name has passed"
+            );
+            name_data
+        }
+
+        /// Removes the primary name of the given `address` if it is the name represented by the DomainName NFT with the given `id`.
+        fn clear_primary_name(&mut self, address: Address, id: u128) {
+            if self.primary_names.get(&address).flatten() == Some(id) {
+                self.primary_names.insert(address, None);
+            }
+        }
+
+        /// Panics if the given `name` is registered and its grace period has not passed yet.
+        fn check_available(&self, name: &str) {
+            if let Some(id) = self.get_registration(Self::hash_name(name.to_string())) {
+                if let Some(last_valid_epoch) = self.get_last_valid_epoch(id, name) {
+                    assert!(
+                        Context::current_epoch() > last_valid_epoch + GRACE_PERIOD_EPOCHS,
+                        "The name is already registered"
+                    );
+                }
+            }
+        }
+
+        /// Returns the ID for a new DomainName NFT of the given `name` and records it as the current registration.
+        ///
+        /// The first registration of a name uses its hash as NFT ID, re-registrations get a new ID
+        /// because the previous DomainName NFT may still exist.
+        fn register_nft_id(&mut self, name: &str) -> u128 {
+            let hash = Self::hash_name(name.to_string());
+            let registration_count = self.registration_counts.get(&hash).unwrap_or(0);
+            let id = if registration_count == 0 {
+                hash
+            } else {
+                Self::hash_name(format!("{}#{}", name, registration_count))
+            };
+            self.registration_counts.insert(hash, registration_count + 1);
+            self.registrations.insert(hash, Some(id));
+            id
+        }
+
+        /// Returns the ID of the DomainName NFT of the current registration of the name with the given `hash`, if any.
+        fn get_registration(&self, hash: u128) -> Option<u128> {
+            self.registrations.get(&hash).flatten()
+        }
+
+        /// Panics if the given `record_type` is unknown.
+        fn check_record_type(record_type: &str) {
+            assert!(
+                ["component", "resource", "text", "public_key"].contains(&record_type),
+                "The record type must be one of 'component', 'resource', 'text' or 'public_key'"
+            );
+        }
+
+        /// Returns the type of the given `record`, as used by `set_record` and `lookup_record`.
+        fn record_type(record: &Record) -> &'static str {
+            match record {
+                Record::ComponentAddress(_) => "component",
+                Record::ResourceAddress(_) => "resource",
+                Record::Text(_) => "text",
+                Record::PublicKey(_) => "public_key",
+            }
+        }
+
+        /// Parses the `value` of a record of the given `record_type`.
+        fn parse_record(record_type: &str, value: String) -> Record {
+            if record_type == "text" {
+                return Record::Text(value);
+            }
+
+            let address = Address::from_str(&value).expect("The record must be a valid address");
+            match (record_type, address) {
+                ("component", Address::Component(_)) => Record::ComponentAddress(address),
+                ("resource", Address::ResourceDef(_)) => Record::ResourceAddress(address),
+                ("public_key", Address::PublicKey(_)) => Record::PublicKey(address),
+                _ => panic!("The address does not match the record type '{}'", record_type),
+            }
+        }
+
+        /// Returns the canonical form of the given `name` and panics if it is invalid.
+        /// Every name that is passed to a method must be normalized before it is hashed.
+        fn normalize(name: &str) -> String {
+            normalize_name(name).unwrap_or_else(|error| panic!("{}", error))
         }
 
         /// Calculates a hash for the given `name`.
+        /// The name must be in its canonical form, see `normalize_name`.
         ///
         /// The hash is calculated by applying SHA256 to the given name
         /// and then taking the outputs leftmost bytes to construct a u128
@@ -239,3 +1101,52 @@ blueprint! {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("dan.xrd"), Ok("dan.xrd".to_string()));
+        assert_eq!(normalize_name("Dan.XRD"), Ok("dan.xrd".to_string()));
+        assert_eq!(normalize_name("  dan.xrd\n"), Ok("dan.xrd".to_string()));
+        assert_eq!(normalize_name("Pay.Dan.xrd"), Ok("pay.dan.xrd".to_string()));
+        assert_eq!(normalize_name("my-shop42.xrd"), Ok("my-shop42.xrd".to_string()));
+    }
+
+    #[test]
+    fn test_normalize_name_rejects_invalid_names() {
+        assert!(normalize_name("dan").is_err());
+        assert!(normalize_name("dan.com").is_err());
+        assert!(normalize_name(".xrd").is_err());
+        assert!(normalize_name("pay..dan.xrd").is_err());
+        assert!(normalize_name("d an.xrd").is_err());
+        assert!(normalize_name("dan_1.xrd").is_err());
+        assert!(normalize_name("-dan.xrd").is_err());
+        assert!(normalize_name("dan-.xrd").is_err());
+        // The Cyrillic 'а' looks like the Latin 'a'
+        assert!(normalize_name("d\u{0430}n.xrd").is_err());
+        assert!(normalize_name("dän.xrd").is_err());
+    }
+
+    #[test]
+    fn test_normalize_name_length_limits() {
+        let label = "a".repeat(MAX_LABEL_LENGTH);
+        assert!(normalize_name(&format!("{}.xrd", label)).is_ok());
+        assert!(normalize_name(&format!("a{}.xrd", label)).is_err());
+
+        let name = format!("{}.{}.{}.{}.xrd", label, label, label, "a".repeat(57));
+        assert_eq!(name.len(), MAX_NAME_LENGTH);
+        assert!(normalize_name(&name).is_ok());
+        assert!(normalize_name(&format!("a{}", name)).is_err());
+    }
+
+    #[test]
+    fn test_normalize_label() {
+        assert_eq!(normalize_label("Pay"), Ok("pay".to_string()));
+        assert!(normalize_label("").is_err());
+        assert!(normalize_label("pay.dan").is_err());
+        assert!(normalize_label("pay!").is_err());
+    }
+}
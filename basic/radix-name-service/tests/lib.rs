@@ -0,0 +1,448 @@
+use radix_engine::ledger::*;
+use radix_engine::transaction::*;
+use scrypto::prelude::*;
+use sha2::{Digest, Sha256};
+
+#[test]
+fn test_register_name() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let user = test_env.new_user();
+    let other = test_env.new_user();
+
+    // "dan" is a short name, so a premium of 100 per year is charged on top of the deposit of 50 per year
+    let receipt = test_env.register(user, "dan.xrd", user.account, 10, "1600");
+    assert!(receipt.success);
+    test_env.assert_balance(user.account, RADIX_TOKEN, "998500");
+    test_env.assert_balance(user.account, test_env.name_resource, "1");
+
+    // Names are normalized before they are looked up
+    assert!(test_env.call_method(other, "lookup_address", vec!["DAN.xrd".to_string()]).success);
+    assert!(!test_env.call_method(other, "lookup_address", vec!["nobody.xrd".to_string()]).success);
+
+    // The name is taken, subdomains and invalid names can't be registered and the deposit must be sufficient
+    assert!(!test_env.register(other, "Dan.xrd", other.account, 1, "150").success);
+    assert!(!test_env.register(other, "pay.dan.xrd", other.account, 1, "150").success);
+    assert!(!test_env.register(other, "dan", other.account, 1, "150").success);
+    assert!(!test_env.register(other, "other-name.xrd", other.account, 1, "49").success);
+
+    // Unregistering returns the deposit, but not the premium
+    let receipt = test_env.call_method(user, "unregister_name", vec![test_env.name_nft(name_id("dan.xrd"))]);
+    assert!(receipt.success);
+    test_env.assert_balance(user.account, RADIX_TOKEN, "999000");
+    assert!(!test_env.call_method(other, "lookup_address", vec!["dan.xrd".to_string()]).success);
+
+    // A re-registration gets a new NFT ID, because the previous DomainName NFT may still exist
+    assert!(test_env.register(other, "dan.xrd", other.account, 1, "150").success);
+    let receipt = test_env.call_method(other, "update_address", vec![
+        test_env.name_nft(reregistration_id("dan.xrd", 1)),
+        user.account.to_string(),
+        format!("10,{}", RADIX_TOKEN),
+    ]);
+    assert!(receipt.success);
+    test_env.assert_balance(other.account, RADIX_TOKEN, "999840");
+}
+
+#[test]
+fn test_expiry_and_grace_period() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let user = test_env.new_user();
+    let other = test_env.new_user();
+    let name_nft = test_env.name_nft(name_id("alice-shop.xrd"));
+
+    // Registered for one year, which ends at epoch 15000
+    assert!(test_env.register(user, "alice-shop.xrd", user.account, 1, "50").success);
+    test_env.set_current_epoch(15000);
+    assert!(test_env.call_method(other, "lookup_address", vec!["alice-shop.xrd".to_string()]).success);
+
+    // An expired name no longer resolves, but during the grace period only its owner can renew it
+    test_env.set_current_epoch(15001);
+    assert!(!test_env.call_method(other, "lookup_address", vec!["alice-shop.xrd".to_string()]).success);
+    assert!(!test_env.register(other, "alice-shop.xrd", other.account, 1, "50").success);
+    let receipt = test_env.call_method(user, "renew_name", vec![name_nft.clone(), "1".to_string(), format!("25,{}", RADIX_TOKEN)]);
+    assert!(receipt.success);
+    assert!(test_env.call_method(other, "lookup_address", vec!["alice-shop.xrd".to_string()]).success);
+    test_env.assert_balance(user.account, RADIX_TOKEN, "999925");
+
+    // After the grace period of the renewed registration, anyone may register the name again
+    test_env.set_current_epoch(30000 + 3750 + 1);
+    let receipt = test_env.call_method(user, "renew_name", vec![name_nft.clone(), "1".to_string(), format!("25,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+    assert!(test_env.register(other, "alice-shop.xrd", other.account, 1, "50").success);
+    assert!(test_env.call_method(user, "lookup_address", vec!["alice-shop.xrd".to_string()]).success);
+
+    // The previous owner can no longer use their DomainName NFT, but can still get the deposit back
+    let receipt = test_env.call_method(user, "update_address", vec![name_nft.clone(), user.account.to_string(), format!("10,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+    assert!(test_env.call_method(user, "unregister_name", vec![name_nft]).success);
+    test_env.assert_balance(user.account, RADIX_TOKEN, "999975");
+    assert!(test_env.call_method(user, "lookup_address", vec!["alice-shop.xrd".to_string()]).success);
+}
+
+#[test]
+fn test_subdomains_and_records() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let user = test_env.new_user();
+    let other = test_env.new_user();
+    let name_nft = test_env.name_nft(name_id("dan-shop.xrd"));
+
+    assert!(test_env.register(user, "dan-shop.xrd", user.account, 1, "50").success);
+
+    // Only the owner of the parent domain can register a subdomain, and only once
+    let receipt = test_env.call_method(other, "register_subdomain", vec![name_nft.clone(), "pay".to_string(), other.account.to_string()]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(user, "register_subdomain", vec![name_nft.clone(), "Pay".to_string(), other.account.to_string()]);
+    assert!(receipt.success);
+    test_env.assert_balance(user.account, test_env.name_resource, "2");
+    let receipt = test_env.call_method(user, "register_subdomain", vec![name_nft.clone(), "pay".to_string(), user.account.to_string()]);
+    assert!(!receipt.success);
+    assert!(test_env.call_method(other, "lookup_address", vec!["pay.dan-shop.xrd".to_string()]).success);
+
+    // Subdomains can't be renewed on their own
+    let subdomain_nft = test_env.name_nft(name_id("pay.dan-shop.xrd"));
+    let receipt = test_env.call_method(user, "renew_name", vec![subdomain_nft, "1".to_string(), format!("25,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+
+    // Every record must match its type
+    let receipt = test_env.call_method(user, "set_record", vec![name_nft.clone(), "text".to_string(), "https://dan.example".to_string(), format!("10,{}", RADIX_TOKEN)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(user, "set_record", vec![name_nft.clone(), "component".to_string(), user.account.to_string(), format!("10,{}", RADIX_TOKEN)]);
+    assert!(receipt.success);
+    let receipt = test_env.call_method(user, "set_record", vec![name_nft.clone(), "resource".to_string(), user.account.to_string(), format!("10,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(user, "set_record", vec![name_nft, "email".to_string(), "dan@example.com".to_string(), format!("10,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+    test_env.assert_balance(user.account, RADIX_TOKEN, "999930");
+
+    assert!(test_env.call_method(other, "lookup_record", vec!["dan-shop.xrd".to_string(), "text".to_string()]).success);
+    assert!(!test_env.call_method(other, "lookup_record", vec!["dan-shop.xrd".to_string(), "email".to_string()]).success);
+
+    // Subdomains expire together with their parent domain
+    test_env.set_current_epoch(15001);
+    assert!(!test_env.call_method(other, "lookup_address", vec!["pay.dan-shop.xrd".to_string()]).success);
+    assert!(!test_env.call_method(other, "lookup_record", vec!["dan-shop.xrd".to_string(), "text".to_string()]).success);
+}
+
+#[test]
+fn test_fee_schedule_and_premiums() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let admin = test_env.admin;
+    let user = test_env.new_user();
+    let admin_badge = format!("1,{}", test_env.admin_badge);
+
+    // Only the admin can change the fees
+    let receipt = test_env.call_method(user, "set_fee_schedule", vec!["0".to_string(), "0".to_string(), "0".to_string(), admin_badge.clone()]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(admin, "set_fee_schedule", vec!["100".to_string(), "20".to_string(), "30".to_string(), admin_badge.clone()]);
+    assert!(receipt.success);
+
+    // Only names with up to 4 characters are charged a premium
+    let receipt = test_env.call_method(admin, "set_short_name_premium", vec!["5".to_string(), "10".to_string(), admin_badge.clone()]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(admin, "set_short_name_premium", vec!["3".to_string(), "10".to_string(), admin_badge.clone()]);
+    assert!(receipt.success);
+
+    // Registering "bob.xrd" for a year now costs a deposit of 100 and a premium of 10
+    assert!(!test_env.register(user, "bob.xrd", user.account, 1, "109").success);
+    assert!(test_env.register(user, "bob.xrd", user.account, 1, "200").success);
+    test_env.assert_balance(user.account, RADIX_TOKEN, "999890");
+
+    let name_nft = test_env.name_nft(name_id("bob.xrd"));
+    let receipt = test_env.call_method(user, "update_address", vec![name_nft.clone(), admin.account.to_string(), format!("19,{}", RADIX_TOKEN)]);
+    assert!(!receipt.success);
+    let receipt = test_env.call_method(user, "update_address", vec![name_nft.clone(), admin.account.to_string(), format!("20,{}", RADIX_TOKEN)]);
+    assert!(receipt.success);
+
+    // The premium is charged for the renewal too
+    let receipt = test_env.call_method(user, "renew_name", vec![name_nft, "1".to_string(), format!("50,{}", RADIX_TOKEN)]);
+    assert!(receipt.success);
+    test_env.assert_balance(user.account, RADIX_TOKEN, "999830");
+
+    // The admin collects the premiums and fees, but not the deposit
+    assert!(!test_env.call_method(user, "withdraw_fees", vec![admin_badge.clone()]).success);
+    assert!(test_env.call_method(admin, "withdraw_fees", vec![admin_badge]).success);
+    test_env.assert_balance(admin.account, RADIX_TOKEN, "1000070");
+}
+
+#[test]
+fn test_reserved_names() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let admin = test_env.admin;
+    let user = test_env.new_user();
+    let admin_badge = format!("1,{}", test_env.admin_badge);
+
+    assert!(!test_env.call_method(user, "reserve_name", vec!["radix.xrd".to_string(), admin_badge.clone()]).success);
+    assert!(!test_env.call_method(admin, "reserve_name", vec!["radix".to_string(), admin_badge.clone()]).success);
+    assert!(test_env.call_method(admin, "reserve_name", vec!["Radix.xrd".to_string(), admin_badge.clone()]).success);
+    assert!(!test_env.register(user, "radix.xrd", user.account, 1, "50").success);
+
+    assert!(test_env.call_method(admin, "unreserve_name", vec!["radix.xrd".to_string(), admin_badge.clone()]).success);
+    assert!(!test_env.call_method(admin, "unreserve_name", vec!["radix.xrd".to_string(), admin_badge]).success);
+    assert!(test_env.register(user, "radix.xrd", user.account, 1, "50").success);
+}
+
+#[test]
+fn test_primary_names() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let user = test_env.new_user();
+    let other = test_env.new_user();
+    let user_name_nft = test_env.name_nft(name_id("primary-one.xrd"));
+    let other_name_nft = test_env.name_nft(name_id("primary-two.xrd"));
+
+    assert!(test_env.register(user, "primary-one.xrd", user.account, 1, "50").success);
+    assert!(test_env.call_method(user, "set_primary_name", vec![user_name_nft.clone()]).success);
+
+    // The last owner of a name that maps to the address sets the primary name, so a third party can't lock it
+    assert!(test_env.register(other, "primary-two.xrd", user.account, 1, "50").success);
+    assert!(test_env.call_method(other, "set_primary_name", vec![other_name_nft.clone()]).success);
+    assert!(!test_env.call_method(user, "unset_primary_name", vec![user_name_nft.clone()]).success);
+    assert!(test_env.call_method(user, "set_primary_name", vec![user_name_nft.clone()]).success);
+
+    // Only the owner of the primary name can unset it
+    assert!(!test_env.call_method(other, "unset_primary_name", vec![other_name_nft]).success);
+    assert!(test_env.call_method(user, "unset_primary_name", vec![user_name_nft]).success);
+
+    // Only the owner of a public key can set its primary name
+    let key_name_nft = test_env.name_nft(name_id("primary-key.xrd"));
+    assert!(test_env.register(other, "primary-key.xrd", user.key, 1, "50").success);
+    assert!(!test_env.call_method(other, "set_primary_name", vec![key_name_nft]).success);
+}
+
+#[test]
+fn test_purchase_name() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let admin = test_env.admin;
+    let seller = test_env.new_user();
+    let buyer = test_env.new_user();
+    let admin_badge = format!("1,{}", test_env.admin_badge);
+    let name_nft = test_env.name_nft(name_id("market.xrd"));
+    let listing_receipt = format!("1,{}", test_env.listing_resource);
+
+    assert!(test_env.call_method(admin, "set_royalty", vec!["10".to_string(), admin_badge.clone()]).success);
+    assert!(test_env.register(seller, "market.xrd", seller.account, 1, "50").success);
+    assert!(test_env.call_method(seller, "list_name", vec![name_nft.clone(), "100".to_string()]).success);
+    test_env.assert_balance(seller.account, test_env.listing_resource, "1");
+
+    // The buyer must pay at least the price and gets the change back
+    assert!(!test_env.call_method(buyer, "purchase_name", vec!["market.xrd".to_string(), format!("90,{}", RADIX_TOKEN)]).success);
+    assert!(test_env.call_method(buyer, "purchase_name", vec!["Market.xrd".to_string(), format!("150,{}", RADIX_TOKEN)]).success);
+    test_env.assert_balance(buyer.account, RADIX_TOKEN, "999900");
+    test_env.assert_balance(buyer.account, test_env.name_resource, "1");
+    assert!(!test_env.call_method(admin, "purchase_name", vec!["market.xrd".to_string(), format!("150,{}", RADIX_TOKEN)]).success);
+
+    // The buyer now owns the name
+    let receipt = test_env.call_method(buyer, "update_address", vec![name_nft.clone(), buyer.account.to_string(), format!("10,{}", RADIX_TOKEN)]);
+    assert!(receipt.success);
+
+    // The seller receives the price minus the royalty of 10%, which goes to the fees
+    assert!(test_env.call_method(seller, "close_listing", vec![listing_receipt.clone()]).success);
+    test_env.assert_balance(seller.account, RADIX_TOKEN, "1000040");
+    test_env.assert_balance(seller.account, test_env.listing_resource, "0");
+    assert!(test_env.call_method(admin, "withdraw_fees", vec![admin_badge]).success);
+    test_env.assert_balance(admin.account, RADIX_TOKEN, "1000020");
+
+    // The deposit is passed on to the buyer
+    assert!(test_env.call_method(buyer, "unregister_name", vec![name_nft]).success);
+    test_env.assert_balance(buyer.account, RADIX_TOKEN, "999940");
+}
+
+#[test]
+fn test_offers() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let seller = test_env.new_user();
+    let buyer1 = test_env.new_user();
+    let buyer2 = test_env.new_user();
+    let listing_receipt = format!("1,{}", test_env.listing_resource);
+    let offer_receipt = format!("1,{}", test_env.offer_resource);
+
+    assert!(test_env.register(seller, "offer-name.xrd", seller.account, 1, "50").success);
+    let name_nft = test_env.name_nft(name_id("offer-name.xrd"));
+    assert!(test_env.call_method(seller, "list_name", vec![name_nft, "1000".to_string()]).success);
+
+    // Offers are held until they are accepted or withdrawn
+    assert!(test_env.call_method(buyer1, "make_offer", vec!["offer-name.xrd".to_string(), format!("80,{}", RADIX_TOKEN)]).success);
+    assert!(test_env.call_method(buyer2, "make_offer", vec!["offer-name.xrd".to_string(), format!("90,{}", RADIX_TOKEN)]).success);
+    test_env.assert_balance(buyer1.account, RADIX_TOKEN, "999920");
+    assert!(!test_env.call_method(buyer1, "make_offer", vec!["unlisted.xrd".to_string(), format!("80,{}", RADIX_TOKEN)]).success);
+
+    // A withdrawn offer can't be accepted anymore
+    assert!(test_env.call_method(buyer1, "close_offer", vec![offer_receipt.clone()]).success);
+    test_env.assert_balance(buyer1.account, RADIX_TOKEN, "1000000");
+    assert!(!test_env.call_method(seller, "accept_offer", vec!["0".to_string(), listing_receipt.clone()]).success);
+
+    // Only the seller can accept an offer
+    assert!(!test_env.call_method(buyer1, "accept_offer", vec!["1".to_string(), listing_receipt.clone()]).success);
+    assert!(test_env.call_method(seller, "accept_offer", vec!["1".to_string(), listing_receipt.clone()]).success);
+
+    // The name is sold
+    assert!(!test_env.call_method(buyer1, "purchase_name", vec!["offer-name.xrd".to_string(), format!("1000,{}", RADIX_TOKEN)]).success);
+    assert!(!test_env.call_method(buyer1, "make_offer", vec!["offer-name.xrd".to_string(), format!("100,{}", RADIX_TOKEN)]).success);
+    assert!(!test_env.call_method(seller, "update_price", vec!["500".to_string(), listing_receipt.clone()]).success);
+
+    // The buyer claims the name and the seller receives the offer minus the royalty of 5%
+    assert!(test_env.call_method(buyer2, "close_offer", vec![offer_receipt]).success);
+    test_env.assert_balance(buyer2.account, test_env.name_resource, "1");
+    test_env.assert_balance(buyer2.account, RADIX_TOKEN, "999910");
+    assert!(test_env.call_method(seller, "close_listing", vec![listing_receipt]).success);
+    test_env.assert_balance(seller.account, RADIX_TOKEN, "1000035.5");
+}
+
+#[test]
+fn test_close_listing_and_expiry() {
+    let mut ledger = InMemoryLedger::with_bootstrap();
+    let mut test_env = TestEnv::new(&mut ledger);
+    let seller = test_env.new_user();
+    let buyer = test_env.new_user();
+    let listing_receipt = format!("1,{}", test_env.listing_resource);
+    let name_nft = test_env.name_nft(name_id("expiring.xrd"));
+
+    // Closing an unsold listing returns the DomainName NFT
+    assert!(test_env.register(seller, "expiring.xrd", seller.account, 1, "50").success);
+    assert!(test_env.call_method(seller, "list_name", vec![name_nft.clone(), "100".to_string()]).success);
+    test_env.assert_balance(seller.account, test_env.name_resource, "0");
+    assert!(test_env.call_method(seller, "close_listing", vec![listing_receipt.clone()]).success);
+    test_env.assert_balance(seller.account, test_env.name_resource, "1");
+    assert!(!test_env.call_method(buyer, "purchase_name", vec!["expiring.xrd".to_string(), format!("100,{}", RADIX_TOKEN)]).success);
+
+    // An expired name can't be bought
+    assert!(test_env.call_method(seller, "list_name", vec![name_nft, "100".to_string()]).success);
+    test_env.set_current_epoch(15001);
+    assert!(!test_env.call_method(buyer, "purchase_name", vec!["expiring.xrd".to_string(), format!("100,{}", RADIX_TOKEN)]).success);
+    assert!(!test_env.call_method(buyer, "make_offer", vec!["expiring.xrd".to_string(), format!("100,{}", RADIX_TOKEN)]).success);
+
+    // Until the seller renews it while it is listed
+    let receipt = test_env.call_method(seller, "renew_listed_name", vec!["1".to_string(), format!("25,{}", RADIX_TOKEN), listing_receipt]);
+    assert!(receipt.success);
+    assert!(test_env.call_method(buyer, "purchase_name", vec!["expiring.xrd".to_string(), format!("100,{}", RADIX_TOKEN)]).success);
+    assert!(test_env.call_method(buyer, "lookup_address", vec!["expiring.xrd".to_string()]).success);
+}
+
+// The ID of the DomainName NFT of the first registration of the given name, see `hash_name` in the blueprint
+fn name_id(name: &str) -> u128 {
+    let hash = Sha256::digest(name.as_bytes());
+    let mut truncated_hash: [u8; 16] = Default::default();
+    truncated_hash.copy_from_slice(&hash[..16]);
+    u128::from_le_bytes(truncated_hash)
+}
+
+// The ID of the DomainName NFT of a re-registration of the given name, see `register_nft_id` in the blueprint
+fn reregistration_id(name: &str, registration_count: u64) -> u128 {
+    name_id(&format!("{}#{}", name, registration_count))
+}
+
+#[derive(Copy, Clone)]
+struct User {
+    key: Address,
+    account: Address,
+}
+
+struct TestEnv<'a> {
+    executor: TransactionExecutor<'a, InMemoryLedger>,
+    admin: User,
+    component: Address,
+    admin_badge: Address,
+    name_resource: Address,
+    listing_resource: Address,
+    offer_resource: Address,
+}
+
+impl<'a> TestEnv<'a> {
+    // Publishes the package and instantiates the RNS with a new admin user
+    pub fn new(ledger: &'a mut InMemoryLedger) -> Self {
+        let mut executor = TransactionExecutor::new(ledger, 0, 0);
+        let package = executor.publish_package(include_code!("radix_name_service"));
+        let key = executor.new_public_key();
+        let admin = User { key, account: executor.new_account(key) };
+
+        let tx = TransactionBuilder::new(&executor)
+            .call_function(package, "RadixNameService", "new", vec![], Some(admin.account))
+            .deposit_all_buckets(admin.account)
+            .build(vec![admin.key])
+            .unwrap();
+        let receipt = executor.run(tx, false).unwrap();
+        assert!(receipt.success);
+
+        Self {
+            executor,
+            admin,
+            component: receipt.component(0).unwrap(),
+            admin_badge: receipt.resource_def(0).unwrap(),
+            name_resource: receipt.resource_def(2).unwrap(),
+            listing_resource: receipt.resource_def(3).unwrap(),
+            offer_resource: receipt.resource_def(4).unwrap(),
+        }
+    }
+
+    pub fn new_user(&mut self) -> User {
+        let key = self.executor.new_public_key();
+        User { key, account: self.executor.new_account(key) }
+    }
+
+    pub fn set_current_epoch(&mut self, epoch: u64) {
+        self.executor.set_current_epoch(epoch);
+    }
+
+    // Registers the name for the given number of years, paying with the given amount of XRD
+    pub fn register(&mut self, user: User, name: &str, target_address: Address, years: u8, deposit: &str) -> Receipt {
+        self.call_method(user, "register_name", vec![
+            name.to_string(),
+            target_address.to_string(),
+            years.to_string(),
+            format!("{},{}", deposit, RADIX_TOKEN),
+        ])
+    }
+
+    // The argument for a bucket or bucket ref with the DomainName NFT with the given ID
+    pub fn name_nft(&self, id: u128) -> String {
+        format!("#{},{}", id, self.name_resource)
+    }
+
+    pub fn call_method(&mut self, user: User, method: &str, args: Vec<String>) -> Receipt {
+        let tx = TransactionBuilder::new(&self.executor)
+            .call_method(self.component, method, args, Some(user.account))
+            .deposit_all_buckets(user.account)
+            .drop_all_bucket_refs()
+            .build(vec![user.key])
+            .unwrap();
+        let receipt = self.executor.run(tx, false).unwrap();
+        println!("{:?}\n", receipt);
+        receipt
+    }
+
+    pub fn assert_balance(&self, account: Address, token: Address, expected: &str) {
+        assert_eq!(self.get_balance(account, token), Decimal::from_str(expected).unwrap());
+    }
+
+    // Returns the amount of the token in the account, zero if the account has never held the token
+    fn get_balance(&self, account: Address, token: Address) -> Decimal {
+        let ledger = self.executor.ledger();
+        let account_component = ledger.get_component(account).unwrap();
+        let mut vaults = vec![];
+        let _res = radix_engine::utils::format_data_with_ledger(
+            account_component
+                .state(radix_engine::model::Actor::SuperUser)
+                .unwrap(),
+            ledger,
+            &mut vaults,
+        ).unwrap();
+
+        for vid in vaults {
+            let vault = ledger.get_vault(vid).unwrap();
+            let resource_def_address = vault
+                .resource_address(radix_engine::model::Actor::SuperUser)
+                .unwrap();
+            if token == resource_def_address {
+                return vault.amount(radix_engine::model::Actor::SuperUser).unwrap();
+            }
+        }
+
+        Decimal::zero()
+    }
+}